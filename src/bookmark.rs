@@ -1,17 +1,48 @@
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use sha2::{Digest, Sha256};
+use std::time::SystemTime;
 use std::{collections::HashSet, convert::TryInto, fmt::Display, path::PathBuf, str::FromStr};
 use std::{hash::Hash, path::Path};
 use url::Url;
 
 use crate::tag::Tag;
 
+/// Keys of a bookmark file, one for each field of [Bookmark]
+const KEYS: [&str; 7] = [
+    "url",
+    "title",
+    "description",
+    "tags",
+    "notes",
+    "created",
+    "modified",
+];
+
+/// A bookmark, which is always serialized in the same canonical form: keys in the order of the
+/// fields and tags sorted, so that saving an unchanged bookmark never changes its file
 #[derive(Eq, Serialize, Deserialize, Clone, Debug)]
 pub struct Bookmark {
     url: Url,
     title: Option<String>,
+    /// A short description of what the bookmark is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(serialize_with = "serialize_sorted")]
     tags: HashSet<Tag>,
+    /// Free-form notes on the bookmark, which may span several lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    /// When the bookmark was first saved, which is not known for bookmarks saved by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::time::rfc3339")]
+    created: Option<SystemTime>,
+    /// When the bookmark was last saved
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::time::rfc3339")]
+    modified: Option<SystemTime>,
+}
+
+fn serialize_sorted<S: Serializer>(tags: &HashSet<Tag>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(tags.iter().sorted())
 }
 
 impl Bookmark {
@@ -24,7 +55,15 @@ impl Bookmark {
             Ok(url) => url,
             Err(_) => return Err(Error::InvalidUrl),
         };
-        let bm = Bookmark { url, title, tags };
+        let bm = Bookmark {
+            url,
+            title,
+            description: None,
+            tags,
+            notes: None,
+            created: None,
+            modified: None,
+        };
 
         Ok(bm)
     }
@@ -57,8 +96,22 @@ impl Bookmark {
         })
     }
 
-    fn id(&self) -> String {
-        hash(&self.url.as_ref())
+    /// The bookmark in canonical YAML
+    /// Keys in the YAML of a bookmark file that are not keys of a bookmark, and would be lost if the
+    /// file was rewritten from the bookmark
+    pub fn unknown_keys(yaml: &str) -> Result<Vec<String>, FileError> {
+        let mapping: serde_yaml::Mapping = serde_yaml::from_str(yaml)?;
+        let unknown: Vec<String> = mapping
+            .keys()
+            .map(|key| key.as_str().map(String::from).unwrap_or_else(|| format!("{:?}", key)))
+            .filter(|key| !KEYS.contains(&key.as_str()))
+            .collect();
+
+        Ok(unknown)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, FileError> {
+        serde_yaml::to_string(self).map_err(|_| FileError::Serialize)
     }
 
     pub fn url(&self) -> Url {
@@ -69,11 +122,6 @@ impl Bookmark {
         self.url.domain()
     }
 
-    fn root_domain(&self) -> Option<&str> {
-        let parts: Vec<&str> = self.domain()?.split('.').collect();
-        parts.iter().nth_back(1).copied()
-    }
-
     pub fn title(&self) -> Option<String> {
         self.title.clone()
     }
@@ -82,28 +130,90 @@ impl Bookmark {
         &self.tags
     }
 
-    pub fn terms(&self) -> HashSet<Tag> {
-        let mut terms: HashSet<Tag> = self.tags.clone();
-        if let Some(domain) = self.root_domain() {
-            Tag::new(domain).ok().map(|d| terms.insert(d));
-        };
-        terms
+    pub fn description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    pub fn notes(&self) -> Option<String> {
+        self.notes.clone()
+    }
+
+    pub fn with_url(self, url: Url) -> Self {
+        Bookmark { url, ..self }
+    }
+
+    pub fn with_title(self, title: Option<String>) -> Self {
+        Bookmark { title, ..self }
+    }
+
+    pub fn with_description(self, description: Option<String>) -> Self {
+        Bookmark {
+            description,
+            ..self
+        }
+    }
+
+    pub fn with_tags(self, tags: HashSet<Tag>) -> Self {
+        Bookmark { tags, ..self }
+    }
+
+    pub fn with_notes(self, notes: Option<String>) -> Self {
+        Bookmark { notes, ..self }
+    }
+
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    pub fn with_timestamps(
+        self,
+        created: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> Self {
+        Bookmark {
+            created,
+            modified,
+            ..self
+        }
+    }
+
+    /// Mark the bookmark as modified at the given time, and as created at the given time unless
+    /// it or the prior version of it already has a creation time
+    pub fn touch(self, prior: Option<&Bookmark>, now: SystemTime) -> Bookmark {
+        let created: Option<SystemTime> = earliest(self.created, prior.and_then(|p| p.created));
+        Bookmark {
+            created: created.or(Some(now)),
+            modified: Some(now),
+            ..self
+        }
     }
 
     pub fn rel_path(&self) -> PathBuf {
-        let domain = self.domain().unwrap_or("").to_string();
-        let hash = self.id();
-        let path: String = format!("{}/{}.{}", domain, hash, "yaml");
-        path.into()
+        rel_path(&self.url)
     }
 
+    /// Merge the bookmark with another bookmark with the same URL, where the title of this bookmark
+    /// is used, its description and notes unless it has none, and the tags of both are combined
     pub fn merge(self, other: Bookmark) -> Bookmark {
         if self.url != other.url {
             self
         } else {
             let tags: HashSet<Tag> = self.tags.iter().chain(other.tags.iter()).cloned().collect();
-
-            Bookmark { tags, ..self }
+            let created: Option<SystemTime> = earliest(self.created, other.created);
+            let modified: Option<SystemTime> = self.modified.max(other.modified);
+
+            Bookmark {
+                description: self.description.or(other.description),
+                tags,
+                notes: self.notes.or(other.notes),
+                created,
+                modified,
+                ..self
+            }
         }
     }
 }
@@ -131,7 +241,39 @@ impl Hash for Bookmark {
     }
 }
 
-fn hash(input: &str) -> String {
+fn earliest(a: Option<SystemTime>, b: Option<SystemTime>) -> Option<SystemTime> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// A bookmark for tests, where tags that are not valid are left out
+#[cfg(test)]
+pub(crate) fn make_bookmark(url: &str, title: Option<&str>, tags: &[&str]) -> Bookmark {
+    let tags: HashSet<Tag> = tags.iter().filter_map(|t| Tag::new(*t).ok()).collect();
+    Bookmark::new(url, title.map(String::from), tags).unwrap()
+}
+
+/// A directory for tests in the temporary directory, named after the test process and `name` so
+/// that it is not shared with other tests, which is removed if it exists from an earlier run
+#[cfg(test)]
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("goto-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Path, relative to the data directory, of the file for a bookmark with the given URL
+pub fn rel_path(url: &Url) -> PathBuf {
+    let domain = url.domain().unwrap_or("").to_string();
+    let hash = hash(url.as_ref());
+    let path: String = format!("{}/{}.{}", domain, hash, "yaml");
+    path.into()
+}
+
+pub(crate) fn hash(input: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(input);
     format!("{:02x}", hasher.finalize())
@@ -168,6 +310,8 @@ pub enum FileError {
     Deserialize,
     Serialize,
     IO(String),
+    #[cfg(feature = "sqlite")]
+    Database(String),
 }
 
 impl From<std::io::Error> for FileError {
@@ -190,6 +334,8 @@ impl Display for FileError {
             FileError::Deserialize => String::from("Unable to deserialize"),
             FileError::Serialize => String::from("Unable to serialize"),
             FileError::IO(e) => format!("IO erro: {}", e),
+            #[cfg(feature = "sqlite")]
+            FileError::Database(e) => format!("Database error: {}", e),
             FileError::UnknownExtension => String::from("Unknown extension"),
             FileError::UnsupportedExtension(ext) => match ext.as_str() {
                 "json" => String::from(JSON_ERROR),
@@ -205,3 +351,43 @@ const JSON_ERROR: &str = r#"
     Run 'goto migrate' to migrate all bookmarks files from JSON to YAML.
     See https://github.com/mantono/goto#deprecated-json-support for more information.
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keeps_earliest_creation_time() {
+        let time = |secs: u64| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let bkm = |created: u64, modified: u64| {
+            Bookmark::new("https://example.com", None, HashSet::new())
+                .unwrap()
+                .with_timestamps(Some(time(created)), Some(time(modified)))
+        };
+        let merged: Bookmark = bkm(20, 20).merge(bkm(10, 15));
+        assert_eq!(merged.created(), Some(time(10)));
+        assert_eq!(merged.modified(), Some(time(20)));
+
+        let old = Bookmark::new("https://example.com", None, HashSet::new()).unwrap();
+        let touched: Bookmark = old.touch(Some(&bkm(10, 15)), time(30));
+        assert_eq!(touched.created(), Some(time(10)));
+        assert_eq!(touched.modified(), Some(time(30)));
+    }
+
+    #[test]
+    fn unknown_keys_are_found() {
+        let yaml = "url: https://example.com/\ntitle: null\ntags: []\nrating: 5\n";
+        assert_eq!(Bookmark::unknown_keys(yaml).unwrap(), ["rating"]);
+        let bkm = Bookmark::new("https://example.com", None, HashSet::new()).unwrap();
+        assert!(Bookmark::unknown_keys(&bkm.to_yaml().unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn yaml_is_canonical() {
+        let tags: HashSet<Tag> =
+            ["web", "rust", "docs"].iter().map(|t| Tag::new(*t).unwrap()).collect();
+        let bkm = Bookmark::new("https://example.com", None, tags).unwrap();
+        let yaml = "url: https://example.com/\ntitle: null\ntags:\n- docs\n- rust\n- web\n";
+        assert_eq!(bkm.to_yaml().unwrap(), yaml);
+    }
+}
@@ -1,8 +1,19 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::cmd;
+use crate::{cmd, rules, search, storage, Error};
 use clap::Parser;
 use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+use serde::{Deserialize, Serialize};
+
+/// Name of the configuration file, which is looked for in the configuration directory of goto
+const SETTINGS_FILE: &str = "config.yaml";
+
+/// Environment variables that set the profile and the data directory, when they are not given on
+/// the command line
+const PROFILE_VAR: &str = "GOTO_PROFILE";
+const DIR_VAR: &str = "GOTO_DIR";
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about = "Web bookmarks utility")]
@@ -27,14 +38,260 @@ pub struct Config {
     /// try to figure out if colors are supported by the terminal in the current context, and use it
     /// if possible.
     /// Possible values are "on", "true", "off", "false", "auto".
-    #[clap(long = "colors", default_value = "auto")]
-    colors: Flag,
+    #[clap(long = "colors")]
+    colors: Option<Flag>,
+
+    /// Set storage for bookmarks
+    ///
+    /// Select how bookmarks are stored in the data directory. With "yaml", each bookmark is stored
+    /// in its own YAML file, which is easy to edit by hand and to synchronize with git. With
+    /// "sqlite", all bookmarks are stored in a single SQLite database, which is faster for large
+    /// collections of bookmarks.
+    /// Possible values are "yaml" and "sqlite" (requires feature sqlite).
+    #[clap(long = "storage")]
+    storage: Option<storage::Kind>,
+
+    /// Use bookmarks of profile
+    ///
+    /// Use the bookmarks of the profile with the given name, which are kept separate from the
+    /// bookmarks of any other profile. The bookmarks of a profile are stored in the directory
+    /// configured for it under profiles in the configuration file, or in goto-<profile> in the
+    /// data directory of the user if it is not configured. The profile can also be set with the
+    /// environment variable GOTO_PROFILE.
+    #[clap(short = 'p', long = "profile")]
+    profile: Option<String>,
+
+    /// Use bookmarks in directory
+    ///
+    /// Use the bookmarks in the given directory, such as a git checkout of a shared collection of
+    /// bookmarks. This takes precedence over both the profile and the configuration file. The
+    /// directory can also be set with the environment variable GOTO_DIR, which takes precedence
+    /// over GOTO_PROFILE but not over --profile.
+    #[clap(long = "dir")]
+    dir: Option<PathBuf>,
 
     #[clap(subcommand)]
     pub cmd: Option<cmd::Command>,
 }
 
-impl Config {
+/// Settings read from the configuration file, where any setting that is not present in the file
+/// gets its default value. Options given on the command line take precedence over the settings.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Directory where bookmarks are stored, instead of the default data directory
+    pub dir: Option<PathBuf>,
+    /// Command to run when no command is given
+    pub command: DefaultCommand,
+    /// Minimum score of bookmarks for open, select and explain
+    pub min_score: f64,
+    /// Maximum number of bookmarks for select
+    pub limit: usize,
+    pub colors: Flag,
+    pub storage: storage::Kind,
+    /// Fetch the title of the web page when a bookmark is added or when its title is edited
+    pub fetch_title: bool,
+    pub search: search::Engines,
+    pub list: ListSettings,
+    /// Directories of profiles by name, for profiles that are not stored in the default directory
+    pub profiles: BTreeMap<String, PathBuf>,
+    /// Directories of bookmarks by name that are read along with the bookmarks of the profile,
+    /// but never modified
+    pub sources: BTreeMap<String, PathBuf>,
+    /// Aliases of tags, by the canonical tag that replaces them
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Ignore diacritics when matching tags and keywords, so that `cafe` matches `café`
+    pub fold_diacritics: bool,
+    /// Rules for tagging bookmarks by their URL
+    pub rules: Vec<rules::RuleConfig>,
+    /// Path of the configuration file the settings were read from, if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// Name of the active profile, if any
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// Name of a profile that was given but is not used, since a data directory was also given
+    #[serde(skip)]
+    pub ignored_profile: Option<String>,
+}
+
+/// Data directory and profile given either on the command line or in the environment
+#[derive(Debug, Default)]
+struct Location {
+    dir: Option<PathBuf>,
+    profile: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            dir: None,
+            command: DefaultCommand::Select,
+            min_score: 0.05,
+            limit: 8192,
+            colors: Flag::Auto,
+            storage: storage::Kind::Yaml,
+            fetch_title: true,
+            search: search::Engines::default(),
+            list: ListSettings::default(),
+            profiles: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            fold_diacritics: true,
+            rules: Vec::with_capacity(0),
+            path: None,
+            profile: None,
+            ignored_profile: None,
+        }
+    }
+}
+
+/// Default output format of the list command
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListSettings {
+    pub format: Vec<cmd::FormatField>,
+    pub delimiter: String,
+}
+
+impl Default for ListSettings {
+    fn default() -> Self {
+        ListSettings {
+            format: Vec::with_capacity(0),
+            delimiter: String::from("|"),
+        }
+    }
+}
+
+/// Command that is run when goto is invoked without a command
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultCommand {
+    Select,
+    List,
+}
+
+impl From<DefaultCommand> for cmd::Command {
+    fn from(cmd: DefaultCommand) -> Self {
+        match cmd {
+            DefaultCommand::Select => cmd::Command::Select {
+                min_score: None,
+                limit: None,
+                since: None,
+                before: None,
+                keywords: Vec::with_capacity(0),
+            },
+            DefaultCommand::List => cmd::Command::List {
+                format: None,
+                delimiter: None,
+                since: None,
+                before: None,
+                keywords: Vec::with_capacity(0),
+            },
+        }
+    }
+}
+
+impl Settings {
+    /// Load the settings from the configuration file, or use the default settings if there is no
+    /// configuration file
+    pub fn load() -> Result<Settings, Error> {
+        let path: PathBuf = match settings_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(Settings::default()),
+        };
+        let content: String = std::fs::read_to_string(&path)?;
+        let settings: Settings = serde_yaml::from_str(&content).map_err(|e| {
+            let msg = format!("Invalid configuration file {:?}: {}", path, e);
+            Error::Config(msg)
+        })?;
+
+        Ok(Settings {
+            path: Some(path),
+            ..settings
+        })
+    }
+
+    /// Apply the options given on the command line, which take precedence over the settings
+    pub fn apply(&mut self, cfg: &Config) -> Result<(), Error> {
+        if let Some(colors) = cfg.colors {
+            self.colors = colors;
+        }
+        if let Some(storage) = cfg.storage {
+            self.storage = storage;
+        }
+        let cli = Location {
+            dir: cfg.dir.clone(),
+            profile: cfg.profile.clone(),
+        };
+        let env = Location {
+            dir: std::env::var_os(DIR_VAR).map(PathBuf::from),
+            profile: std::env::var(PROFILE_VAR).ok(),
+        };
+        self.locate(cli, env)
+    }
+
+    /// Select the data directory by the directory and profile given on the command line and in the
+    /// environment. The command line takes precedence over the environment, and a directory takes
+    /// precedence over a profile that is given the same way.
+    fn locate(&mut self, cli: Location, env: Location) -> Result<(), Error> {
+        let (dir, profile, ignored_profile) = match (cli, env) {
+            (
+                Location {
+                    dir: Some(dir),
+                    profile,
+                },
+                env,
+            ) => (Some(dir), None, profile.or(env.profile)),
+            (
+                Location {
+                    profile: Some(profile),
+                    ..
+                },
+                _,
+            ) => (None, Some(profile), None),
+            (
+                _,
+                Location {
+                    dir: Some(dir),
+                    profile,
+                },
+            ) => (Some(dir), None, profile),
+            (_, Location { dir: None, profile }) => (None, profile, None),
+        };
+
+        if let Some(profile) = profile {
+            if !is_valid_profile(&profile) {
+                let msg = format!("Invalid profile name '{}'", profile);
+                return Err(Error::Config(msg));
+            }
+            self.dir = self.profiles.get(&profile).cloned().or_else(|| data_dir(Some(&profile)));
+            self.profile = Some(profile);
+        }
+        if let Some(dir) = dir {
+            self.dir = Some(dir);
+        }
+        self.ignored_profile = ignored_profile;
+
+        Ok(())
+    }
+
+    /// Data directory, which is either the configured directory or the default data directory
+    pub fn dir(&self) -> Option<PathBuf> {
+        match &self.dir {
+            Some(dir) => expand_home(dir),
+            None => data_dir(None),
+        }
+    }
+
+    /// Directories of the read-only sources by name
+    pub fn sources(&self) -> BTreeMap<String, PathBuf> {
+        self.sources
+            .iter()
+            .filter_map(|(name, dir)| expand_home(dir).map(|dir| (name.clone(), dir)))
+            .collect()
+    }
+
     pub fn theme(&self) -> Box<dyn Theme> {
         match self.colors {
             Flag::True => Box::<ColorfulTheme>::default(),
@@ -44,13 +301,64 @@ impl Config {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Flag {
+/// Expand a leading ~ in a configured directory to the home directory of the user
+fn expand_home(dir: &Path) -> Option<PathBuf> {
+    match dir.strip_prefix("~") {
+        Ok(rest) => dirs_next::home_dir().map(|home| home.join(rest)),
+        Err(_) => Some(dir.to_path_buf()),
+    }
+}
+
+/// Default directory of bookmarks, or of the bookmarks of a profile
+fn data_dir(profile: Option<&str>) -> Option<PathBuf> {
+    let name: String = match profile {
+        Some(profile) => format!("goto-{}", profile),
+        None => String::from("goto"),
+    };
+    match dirs_next::data_dir() {
+        Some(dir) => Some(dir.join(name)),
+        None => dirs_next::home_dir().map(|dir| dir.join(format!(".{}", name))),
+    }
+}
+
+/// A profile name is used as part of a directory name, so it must not be able to refer to any
+/// other directory
+fn is_valid_profile(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+fn settings_path() -> Option<PathBuf> {
+    dirs_next::config_dir().map(|dir| dir.join("goto").join(SETTINGS_FILE))
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "FlagValue")]
+pub enum Flag {
     True,
     False,
     Auto,
 }
 
+/// A flag in the configuration file may be given both as a boolean and as a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FlagValue {
+    Bool(bool),
+    Str(String),
+}
+
+impl TryFrom<FlagValue> for Flag {
+    type Error = String;
+
+    fn try_from(value: FlagValue) -> Result<Self, Self::Error> {
+        match value {
+            FlagValue::Bool(true) => Ok(Flag::True),
+            FlagValue::Bool(false) => Ok(Flag::False),
+            FlagValue::Str(s) => Flag::from_str(&s),
+        }
+    }
+}
+
 impl FromStr for Flag {
     type Err = String;
 
@@ -63,3 +371,86 @@ impl FromStr for Flag {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(dir: Option<&str>, profile: Option<&str>) -> Location {
+        Location {
+            dir: dir.map(PathBuf::from),
+            profile: profile.map(String::from),
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            profiles: BTreeMap::from([(String::from("work"), PathBuf::from("/work"))]),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn profile_names_can_not_refer_to_other_directories() {
+        assert!(is_valid_profile("work"));
+        assert!(is_valid_profile("side-project_2"));
+        assert!(!is_valid_profile(""));
+        assert!(!is_valid_profile(".."));
+        assert!(!is_valid_profile("work/../home"));
+        assert!(!is_valid_profile("~"));
+    }
+
+    #[test]
+    fn configured_profile_uses_its_directory() {
+        let mut settings: Settings = settings();
+        settings.locate(location(None, Some("work")), Location::default()).unwrap();
+        assert_eq!(settings.profile.as_deref(), Some("work"));
+        assert_eq!(settings.dir(), Some(PathBuf::from("/work")));
+    }
+
+    #[test]
+    fn unconfigured_profile_uses_default_directory() {
+        let mut settings: Settings = settings();
+        settings.locate(location(None, Some("home")), Location::default()).unwrap();
+        assert_eq!(settings.dir(), data_dir(Some("home")));
+        assert!(settings.dir().unwrap().ends_with("goto-home"));
+    }
+
+    #[test]
+    fn invalid_profile_is_rejected() {
+        let mut settings: Settings = settings();
+        assert!(settings.locate(location(None, Some("../work")), Location::default()).is_err());
+    }
+
+    #[test]
+    fn command_line_takes_precedence_over_environment() {
+        let mut settings: Settings = settings();
+        settings
+            .locate(location(None, Some("work")), location(Some("/env"), None))
+            .unwrap();
+        assert_eq!(settings.profile.as_deref(), Some("work"));
+        assert_eq!(settings.dir(), Some(PathBuf::from("/work")));
+        assert_eq!(settings.ignored_profile, None);
+    }
+
+    #[test]
+    fn directory_takes_precedence_over_profile_and_reports_it() {
+        let mut settings: Settings = settings();
+        settings
+            .locate(location(Some("/cli"), Some("work")), Location::default())
+            .unwrap();
+        assert_eq!(settings.profile, None);
+        assert_eq!(settings.dir(), Some(PathBuf::from("/cli")));
+        assert_eq!(settings.ignored_profile.as_deref(), Some("work"));
+    }
+
+    #[test]
+    fn directory_in_environment_takes_precedence_over_profile_in_environment() {
+        let mut settings: Settings = settings();
+        settings
+            .locate(Location::default(), location(Some("/env"), Some("work")))
+            .unwrap();
+        assert_eq!(settings.dir(), Some(PathBuf::from("/env")));
+        assert_eq!(settings.ignored_profile.as_deref(), Some("work"));
+    }
+}
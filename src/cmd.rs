@@ -1,7 +1,17 @@
 use crate::{
-    bookmark::{self, Bookmark, FileError},
+    bookmark::{self, Bookmark},
+    cfg::Settings,
+    index::{is_hidden, Index},
     io::{self, Streams},
+    query::Query,
+    rank,
+    rules::Rules,
+    search::Engines,
+    storage::{Kind, Storage},
+    suggest,
     tag::{Tag, TagHolder},
+    time::{self, Period},
+    visits::Visits,
     Error,
 };
 use clap::{Subcommand, ValueEnum};
@@ -9,23 +19,40 @@ use dialoguer::{theme::Theme, FuzzySelect, Select};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     io::Write,
     thread::{self, JoinHandle},
 };
-use std::{
-    iter::FromIterator,
-    path::{Path, PathBuf},
-};
 use url::Url;
 
-#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
+mod bulk;
+mod edit;
+mod explain;
+mod import;
+mod tag;
+
+pub use bulk::bulk;
+pub use edit::edit;
+pub use explain::explain;
+pub use import::add_from;
+pub use tag::{retag, tag, TagCommand};
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FormatField {
     Url,
     Title,
     Tags,
     Path,
+    Source,
+    Created,
+    Modified,
+    Description,
+    Notes,
 }
 
 impl std::str::FromStr for FormatField {
@@ -37,6 +64,11 @@ impl std::str::FromStr for FormatField {
             "title" => Ok(FormatField::Title),
             "tags" => Ok(FormatField::Tags),
             "path" => Ok(FormatField::Path),
+            "source" => Ok(FormatField::Source),
+            "created" => Ok(FormatField::Created),
+            "modified" => Ok(FormatField::Modified),
+            "description" => Ok(FormatField::Description),
+            "notes" => Ok(FormatField::Notes),
             other => Err(format!("unknown format field: {}", other)),
         }
     }
@@ -46,40 +78,220 @@ impl std::str::FromStr for FormatField {
 pub enum Command {
     /// Add bookmark with URL
     ///
-    /// Add bookmark with URL and optionally some tags
-    Add { url: String, tags: Vec<Tag> },
+    /// Add bookmark with URL and optionally some tags. The tags and title are prompted for, unless
+    /// --yes is given or the terminal is not interactive.
+    Add {
+        #[clap(required_unless_present_any = &["stdin", "from-file"])]
+        url: Option<String>,
+        tags: Vec<Tag>,
+        /// Title of the bookmark, instead of the title of the web page
+        #[clap(long)]
+        title: Option<String>,
+        /// Add bookmarks from standard input instead, with one URL per line optionally followed by
+        /// tags, such as "github.com git vcs". Prompts are skipped.
+        #[clap(long, conflicts_with_all = &["url", "title", "from-file"])]
+        stdin: bool,
+        /// Add bookmarks from a file instead, in the same format as for --stdin
+        #[clap(long, value_name = "FILE", conflicts_with_all = &["url", "title"])]
+        from_file: Option<PathBuf>,
+        /// Do not fetch the title of the web page
+        #[clap(long)]
+        no_fetch: bool,
+        /// Do not prompt for anything, but save the bookmark with the given tags and title
+        #[clap(short = 'y', long, alias = "no-input")]
+        yes: bool,
+    },
+    /// Change a bookmark
+    ///
+    /// Change the title or tags of an existing bookmark without any prompts, such as
+    /// `goto set github.com --title GitHub --add-tag git --remove-tag misc`. Changes to a bookmark
+    /// of a read-only source are saved to a personal copy of it.
+    Set {
+        url: String,
+        /// New title of the bookmark, or an empty title to remove it
+        #[clap(long)]
+        title: Option<String>,
+        /// Tag to add, may be given several times
+        #[clap(long = "add-tag", value_name = "TAG")]
+        add_tags: Vec<Tag>,
+        /// Tag to remove, may be given several times
+        #[clap(long = "remove-tag", value_name = "TAG")]
+        remove_tags: Vec<Tag>,
+    },
     /// Open bookmark in browser
     ///
     /// Open a bookmark in the browser that is matching the given keywords. If several bookmarks
     /// match the keywords, the best matching bookmark will be selected. If no bookmark is matching
     /// the keywords, the keywords will be directed to a search query in a search engine.
+    /// A keyword such as "!gh" or "!crates" skips the bookmarks and searches directly with the
+    /// search engine of that name.
+    #[clap(allow_hyphen_values = true)]
     Open {
-        #[clap(short = 's', long = "score", default_value = "0.05")]
-        min_score: f64,
-        keywords: Vec<Tag>,
+        /// Minimum score of bookmarks, default 0.05 unless set in the configuration file
+        #[clap(short = 's', long = "score")]
+        min_score: Option<f64>,
+        /// Keywords and filters
+        ///
+        /// Prefix a keyword with - to exclude bookmarks with it, and separate alternatives with |,
+        /// such as "rust|go". Filter on fields with domain:, title:, url: and scheme:, such as
+        /// domain:github.com or title:"release notes". Put -- before the keywords if an excluded
+        /// keyword could be mistaken for an option.
+        keywords: Vec<String>,
     },
     /// Select from a list of bookmarks
     ///
     /// Select from a list of bookmarks
+    #[clap(allow_hyphen_values = true)]
     Select {
-        #[clap(short = 's', long = "score", default_value = "0.05")]
-        min_score: f64,
-        #[clap(short = 'n', long, default_value = "8192")]
-        limit: usize,
-        keywords: Vec<Tag>,
+        /// Minimum score of bookmarks, default 0.05 unless set in the configuration file
+        #[clap(short = 's', long = "score")]
+        min_score: Option<f64>,
+        /// Maximum number of bookmarks, default 8192 unless set in the configuration file
+        #[clap(short = 'n', long)]
+        limit: Option<usize>,
+        /// Only bookmarks created at or after this time, given as a date such as 2024-05-01 or as a
+        /// duration before now such as 7days
+        #[clap(long, value_parser = time::parse)]
+        since: Option<SystemTime>,
+        /// Only bookmarks created before this time, given the same way as for --since
+        #[clap(long, value_parser = time::parse)]
+        before: Option<SystemTime>,
+        /// Keywords and filters
+        ///
+        /// Prefix a keyword with - to exclude bookmarks with it, and separate alternatives with |,
+        /// such as "rust|go". Filter on fields with domain:, title:, url: and scheme:, such as
+        /// domain:github.com or title:"release notes". Put -- before the keywords if an excluded
+        /// keyword could be mistaken for an option.
+        keywords: Vec<String>,
     },
     /// List bookmarks
     ///
-    /// List bookmarks containing all of the provided tags. If no tags are provided, all bookmarks
-    /// are listed. Default output: URL only (one per line). Use --format to customise fields.
-    /// Accepted fields: url, title, tags, path. Example: goto list --format=url,title,tags,path
+    /// List bookmarks containing all of the provided tags and matching all filters. If no tags or
+    /// filters are provided, all bookmarks are listed. Default output: URL only (one per line). Use --format to customise fields.
+    /// Accepted fields: url, title, tags, path, source, created, modified, description, notes.
+    /// Newlines in description and notes are escaped as \n.
+    /// Example: goto list --format=url,title,tags,path
     /// Use --delimiter to set the separator between fields (default: |, no surrounding spaces).
+    /// Defaults for both can be set in the configuration file.
+    #[clap(allow_hyphen_values = true)]
     List {
         #[clap(long, value_delimiter = ',')]
-        format: Vec<FormatField>,
-        #[clap(long, default_value = "|")]
-        delimiter: String,
-        tags: Vec<Tag>,
+        format: Option<Vec<FormatField>>,
+        #[clap(long)]
+        delimiter: Option<String>,
+        /// Only bookmarks created at or after this time, given as a date such as 2024-05-01 or as a
+        /// duration before now such as 7days
+        #[clap(long, value_parser = time::parse)]
+        since: Option<SystemTime>,
+        /// Only bookmarks created before this time, given the same way as for --since
+        #[clap(long, value_parser = time::parse)]
+        before: Option<SystemTime>,
+        /// Keywords and filters
+        ///
+        /// Prefix a keyword with - to exclude bookmarks with it, and separate alternatives with |,
+        /// such as "rust|go". Filter on fields with domain:, title:, url: and scheme:, such as
+        /// domain:github.com or title:"release notes". Put -- before the keywords if an excluded
+        /// keyword could be mistaken for an option.
+        keywords: Vec<String>,
+    },
+    /// Explain the ranking of bookmarks
+    ///
+    /// Show the best matching bookmarks for the given keywords, as they would be ranked by open
+    /// and select, together with how the score of each bookmark was computed: which keywords
+    /// matched which terms in which fields, the weight of each keyword, the contribution of
+    /// frecency and whether the bookmark was cut for having a score below the minimum score.
+    #[clap(allow_hyphen_values = true)]
+    Explain {
+        /// Minimum score of bookmarks, default 0.05 unless set in the configuration file
+        #[clap(short = 's', long = "score")]
+        min_score: Option<f64>,
+        #[clap(short = 'n', long, default_value = "10")]
+        limit: usize,
+        /// Keywords and filters, same as for open and select
+        keywords: Vec<String>,
+    },
+    /// Edit a bookmark in the editor
+    ///
+    /// Open the bookmark that matches the keywords as YAML in the editor given by $VISUAL or
+    /// $EDITOR. If several bookmarks match, the bookmark is first selected from them. The
+    /// bookmark is saved once the editor is closed, and moved to its new file if the URL was
+    /// changed, merged with any bookmark that already has the new URL.
+    #[clap(allow_hyphen_values = true)]
+    Edit {
+        /// Minimum score of bookmarks, default 0.05 unless set in the configuration file
+        #[clap(short = 's', long = "score")]
+        min_score: Option<f64>,
+        /// Keywords and filters, same as for open and select
+        keywords: Vec<String>,
+    },
+    /// Change many bookmarks at once
+    ///
+    /// Add or remove tags, or set the title from the web page, on every bookmark that matches the
+    /// query, such as `goto bulk --where "golang -go" --add-tag go --remove-tag golang`. The
+    /// changes are shown and must be confirmed before they are saved. Bookmarks of read-only
+    /// sources are never changed.
+    Bulk {
+        /// Keywords and filters that the bookmarks must match, the same as for list
+        #[clap(
+            long = "where",
+            value_name = "QUERY",
+            required = true,
+            allow_hyphen_values = true
+        )]
+        query: Vec<String>,
+        /// Tag to add, may be given several times
+        #[clap(long = "add-tag", value_name = "TAG")]
+        add_tags: Vec<Tag>,
+        /// Tag to remove, may be given several times
+        #[clap(long = "remove-tag", value_name = "TAG")]
+        remove_tags: Vec<Tag>,
+        /// Set the title of each bookmark to the title of its web page
+        #[clap(long)]
+        set_title_from_page: bool,
+        /// Only show the changes
+        #[clap(long)]
+        dry_run: bool,
+        /// Save the changes without asking for confirmation
+        #[clap(short = 'y', long)]
+        yes: bool,
+    },
+    /// Manage tags
+    ///
+    /// List, rename, merge and delete tags across all bookmarks. Bookmarks of read-only sources
+    /// are never changed.
+    Tag {
+        #[clap(subcommand)]
+        cmd: TagCommand,
+    },
+    /// Tag bookmarks by rules
+    ///
+    /// Add the tags of every rule in the configuration file that matches the URL of a bookmark to
+    /// the bookmark, for all existing bookmarks. Tags are only added, never removed.
+    Retag {
+        /// Only show which bookmarks would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Print the configuration
+    ///
+    /// Print the effective configuration, which is the values from the configuration file with
+    /// any options given on the command line applied on top of them, in the same format as the
+    /// configuration file.
+    Config,
+    /// Rebuild the search index
+    ///
+    /// Discard the search index and rebuild it by reading every bookmark file in the data
+    /// directory. The index is otherwise refreshed automatically when bookmark files change.
+    Reindex,
+    /// Format bookmark files
+    ///
+    /// Rewrite every bookmark file in the data directory in canonical form, with keys in a fixed
+    /// order and tags sorted, and list the files that changed.
+    Fmt {
+        /// Only list the files that are not in canonical form, and exit with an error if there are
+        /// any, without changing them
+        #[clap(long)]
+        check: bool,
     },
     /// Migrate format of bookmarks
     ///
@@ -88,15 +300,8 @@ pub enum Command {
     Migrate,
 }
 
-impl Default for Command {
-    fn default() -> Self {
-        Command::Select {
-            min_score: 0.05,
-            limit: 8192,
-            keywords: Vec::with_capacity(0),
-        }
-    }
-}
+/// Source of bookmarks in the data directory of the profile, as opposed to read-only sources
+const PERSONAL_SOURCE: &str = "personal";
 
 lazy_static! {
     static ref PROTOCOL_PREFIX: Regex = regex::Regex::new("^https?://").unwrap();
@@ -104,47 +309,117 @@ lazy_static! {
         regex::Regex::new(r"<(title|TITLE)>\s?.*\s?</(title|TITLE)>").unwrap();
 }
 
-fn filter(dir: &Path, keywords: Vec<Tag>, min_score: f64) -> Vec<(f64, Bookmark)> {
-    let keywords: HashSet<Tag> = HashSet::from_iter(keywords);
+/// How much the frecency of a bookmark counts when ranking bookmarks, relative to how well the
+/// bookmark matches the keywords
+const FRECENCY_WEIGHT: f64 = 0.25;
+
+/// A bookmark matching the filters of a query, with its score for the keywords of the query
+struct Candidate {
+    score: rank::Score,
+    /// Frecency of the bookmark, relative to the highest frecency among all candidates that were
+    /// not cut, and weighted by [FRECENCY_WEIGHT]
+    frecency: f64,
+    /// True if the score of the bookmark was below the minimum score
+    cut: bool,
+    bookmark: Bookmark,
+}
+
+impl Candidate {
+    fn total(&self) -> f64 {
+        self.score.value + self.frecency
+    }
+}
+
+/// Rank all bookmarks that match the filters of the query by their score blended with their
+/// frecency, so that among equally good matches the bookmarks that are opened most often and most
+/// recently come first. Bookmarks with a score below `min_score` are marked as cut and come last.
+fn candidates(
+    storage: &dyn Storage,
+    visits: &Visits,
+    query: &Query,
+    min_score: f64,
+) -> Result<Vec<Candidate>, Error> {
+    let keywords: Vec<Vec<Tag>> = query.keywords();
     let min_score: f64 = if keywords.is_empty() { 0.0 } else { min_score };
-    walkdir::WalkDir::new(dir)
+    let bookmarks: Vec<Bookmark> = storage
+        .load_all()?
         .into_iter()
-        .filter_entry(|f| !is_hidden(f))
-        .filter_map(|f| f.ok())
-        .filter(|f| f.file_type().is_file())
-        .filter_map(|f| match Bookmark::from_file(&f.clone().into_path()) {
-            Ok(bkm) => Some(bkm),
-            Err(e) => {
-                log::error!("Unable to read {}: {}", f.path().to_str().unwrap_or_default(), e);
-                None
-            }
+        .map(|(_, bkm)| bkm)
+        .filter(|bkm| query.matches_filters(bkm))
+        .collect();
+    let mut candidates: Vec<Candidate> = rank::rank(bookmarks, &keywords)
+        .into_iter()
+        .map(|(score, bkm)| Candidate {
+            cut: score.value < min_score,
+            frecency: visits.frecency(&bkm.url()),
+            score,
+            bookmark: bkm,
         })
-        .map(|bkm| (score(&bkm.terms(), &keywords), bkm))
-        .filter(|(score, _)| score >= &min_score)
-        .sorted_unstable_by(|b0, b1| b0.0.partial_cmp(&b1.0).unwrap().reverse())
-        .collect_vec()
+        .collect();
+
+    let max_frecency: f64 =
+        candidates.iter().filter(|c| !c.cut).map(|c| c.frecency).fold(0.0, f64::max);
+    for candidate in candidates.iter_mut() {
+        candidate.frecency = match max_frecency {
+            max if max > 0.0 => FRECENCY_WEIGHT * (candidate.frecency / max).min(1.0),
+            _ => 0.0,
+        };
+    }
+
+    candidates.sort_by(|c0, c1| {
+        c0.cut.cmp(&c1.cut).then(c0.total().partial_cmp(&c1.total()).unwrap().reverse())
+    });
+    Ok(candidates)
 }
 
-fn is_hidden(entry: &walkdir::DirEntry) -> bool {
-    entry.file_name().to_str().map(|f| f.starts_with('.')).unwrap_or(false)
+/// Find the bookmarks that match the query with a score of at least `min_score`, ordered by their
+/// score blended with their frecency
+fn filter(
+    storage: &dyn Storage,
+    visits: &Visits,
+    query: &Query,
+    min_score: f64,
+) -> Result<Vec<(f64, Bookmark)>, Error> {
+    let bookmarks = candidates(storage, visits, query, min_score)?
+        .into_iter()
+        .filter(|c| !c.cut)
+        .map(|c| (c.total(), c.bookmark))
+        .collect_vec();
+
+    Ok(bookmarks)
 }
 
 pub fn open(
     mut streams: Streams,
-    dir: &Path,
-    keywords: Vec<Tag>,
+    storage: &dyn Storage,
+    visits: &mut Visits,
+    engines: &Engines,
+    keywords: Vec<String>,
     min_score: f64,
 ) -> Result<(), Error> {
-    let query: String = search_query(&keywords);
-    let bookmarks: Vec<(f64, Bookmark)> = filter(dir, keywords, min_score);
+    if let Some((engine, keywords)) = engines.bang(&keywords) {
+        let url: Url = engines.url(Some(engine), &keywords)?;
+        return open_url(streams, url);
+    }
+
+    let query: Query = Query::parse(&keywords)?;
+    let bookmarks: Vec<(f64, Bookmark)> = filter(storage, visits, &query, min_score)?;
     let url: Url = match bookmarks.first() {
-        Some((_, bookmark)) => bookmark.url(),
+        Some((_, bookmark)) => {
+            visits.record(&bookmark.url())?;
+            bookmark.url()
+        }
         None => {
             writeln!(streams.ui(), "No bookmark found for keyword(s), searching online instead")?;
-            Url::parse(&query).unwrap()
+            engines.url(None, &keywords)?
         }
     };
 
+    open_url(streams, url)
+}
+
+/// Open the URL in the default browser, or print it if that is not possible
+fn open_url(mut streams: Streams, url: Url) -> Result<(), Error> {
     match open::that(url.to_string()) {
         Ok(_) => Ok(()),
         Err(e) => {
@@ -155,21 +430,22 @@ pub fn open(
     }
 }
 
-fn search_query(terms: &[Tag]) -> String {
-    let query: String = terms.iter().map(|t| t.to_string()).join("+");
-    format!("https://duckduckgo.com/?q={}", query)
-}
-
+#[allow(clippy::too_many_arguments)]
 pub fn select(
     mut streams: Streams,
-    dir: &Path,
-    keywords: Vec<Tag>,
+    storage: &dyn Storage,
+    visits: &mut Visits,
+    keywords: Vec<String>,
+    period: Period,
     limit: usize,
     min_score: f64,
+    fetch_title: bool,
     theme: &dyn Theme,
 ) -> Result<(), Error> {
-    let bookmarks: Vec<Bookmark> = filter(dir, keywords, min_score)
+    let query: Query = Query::parse(&keywords)?;
+    let bookmarks: Vec<Bookmark> = filter(storage, visits, &query, min_score)?
         .into_iter()
+        .filter(|(_, bkm)| period.contains(bkm))
         .take(limit)
         .map(|(_, bkm)| bkm)
         .collect();
@@ -186,138 +462,380 @@ pub fn select(
         .interact_on_opt(streams.term())?;
 
     match selection {
-        Some(i) => select_action(streams, dir, bookmarks[i].clone(), theme),
+        Some(i) => {
+            let bookmark: Bookmark = bookmarks[i].clone();
+            select_action(streams, storage, visits, bookmark, fetch_title, theme)
+        }
         None => Ok(()),
     }
 }
 
+/// Actions that can be taken on a bookmark selected with `goto select`, in the order they are listed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Action {
+    Open,
+    EditTitle,
+    EditTags,
+    EditDescription,
+    EditNotes,
+    EditInEditor,
+    EditUrl,
+    Delete,
+    Exit,
+}
+
+impl Action {
+    const ALL: [Action; 9] = [
+        Action::Open,
+        Action::EditTitle,
+        Action::EditTags,
+        Action::EditDescription,
+        Action::EditNotes,
+        Action::EditInEditor,
+        Action::EditUrl,
+        Action::Delete,
+        Action::Exit,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::Open => "open",
+            Action::EditTitle => "edit title",
+            Action::EditTags => "edit tags",
+            Action::EditDescription => "edit description",
+            Action::EditNotes => "edit notes",
+            Action::EditInEditor => "edit in editor",
+            Action::EditUrl => "edit URL",
+            Action::Delete => "delete",
+            Action::Exit => "exit",
+        }
+    }
+}
+
 fn select_action(
     mut streams: Streams,
-    dir: &Path,
+    storage: &dyn Storage,
+    visits: &mut Visits,
     bookmark: Bookmark,
+    fetch_title: bool,
     theme: &dyn Theme,
 ) -> Result<(), Error> {
-    let actions = vec![
-        "open",
-        "edit title",
-        "edit tags",
-        "edit URL",
-        "delete",
-        "exit",
-    ];
-    let selection: Option<usize> = Select::with_theme(theme)
+    let actions: Vec<&str> = Action::ALL.iter().map(|action| action.name()).collect();
+    let selection: Option<Action> = Select::with_theme(theme)
         .with_prompt("Select action")
         .default(0)
         .items(&actions)
-        .interact_on_opt(streams.term())?;
+        .interact_on_opt(streams.term())?
+        .map(|i| Action::ALL[i]);
+
+    // Bookmarks of read-only sources can not be modified, but edits to their title, tags,
+    // description or notes are saved to a personal copy of the bookmark instead
+    let source: Option<&str> = storage.source(&bookmark.url())?;
+    match (selection, source) {
+        (
+            Some(
+                Action::EditTitle | Action::EditTags | Action::EditDescription | Action::EditNotes,
+            ),
+            Some(source),
+        ) => {
+            let msg =
+                format!("Bookmark is from read-only source {}, saving a personal copy", source);
+            writeln!(streams.ui(), "{}", msg)?;
+        }
+        (Some(Action::EditUrl | Action::Delete), Some(source)) => {
+            let msg =
+                format!("Bookmark is from read-only source {} and can not be changed", source);
+            writeln!(streams.ui(), "{}", msg)?;
+            return Ok(());
+        }
+        _ => {}
+    }
 
     match selection {
-        Some(0) => {
+        Some(Action::Open) => {
             open::that(bookmark.url().to_string())?;
+            visits.record(&bookmark.url())?;
         }
-        Some(1) => {
+        Some(Action::EditTitle) => {
             let title: Option<String> = match bookmark.title() {
                 Some(title) => Some(title),
-                None => load_title(&bookmark.url()).join().unwrap(),
+                None if fetch_title => load_title(&bookmark.url()).join().unwrap(),
+                None => None,
             };
             let title: Option<String> = io::read_title(title, theme, streams.term());
-            let bookmark = Bookmark::new(bookmark.url(), title, bookmark.tags().clone()).unwrap();
-            save_bookmark(dir, bookmark, true)?;
+            save_changes(&mut streams, storage, &bookmark, bookmark.clone().with_title(title))?;
+        }
+        Some(Action::EditTags) => {
+            let bookmarks: Vec<(PathBuf, Bookmark)> = storage.load_all()?;
+            let known: HashMap<Tag, usize> =
+                crate::tag::count(bookmarks.iter().map(|(_, b)| b.tags()));
+            let suggest = |tags: &HashSet<Tag>| {
+                suggest::suggest(bookmarks.iter().map(|(_, b)| b), &bookmark.url(), tags)
+            };
+            let tags: HashSet<Tag> =
+                io::read_tags(bookmark.tags().clone(), &known, &suggest, theme, streams.term());
+            save_changes(&mut streams, storage, &bookmark, bookmark.clone().with_tags(tags))?;
         }
-        Some(2) => {
-            let tags = io::read_tags(bookmark.tags().clone(), theme, streams.term());
-            let bookmark = Bookmark::new(bookmark.url(), None, tags).unwrap();
-            save_bookmark(dir, bookmark, false)?;
+        Some(Action::EditDescription) => {
+            let description = io::read_description(bookmark.description(), theme, streams.term());
+            let changed: Bookmark = bookmark.clone().with_description(description);
+            save_changes(&mut streams, storage, &bookmark, changed)?;
         }
-        Some(3) => {
+        Some(Action::EditNotes) => match io::edit_notes(bookmark.notes())? {
+            Some(notes) => {
+                let changed: Bookmark = bookmark.clone().with_notes(notes);
+                save_changes(&mut streams, storage, &bookmark, changed)?;
+            }
+            None => writeln!(streams.ui(), "Notes were not saved, keeping them unchanged")?,
+        },
+        Some(Action::EditInEditor) => edit::edit_bookmark(streams, storage, bookmark, theme)?,
+        Some(Action::EditUrl) => {
             let url = io::read_url(bookmark.url(), theme, streams.term());
             if url != bookmark.url() {
-                let new_bookmark = Bookmark::new(url, None, bookmark.tags().clone()).unwrap();
-                save_bookmark(dir, new_bookmark, true)?;
-                delete_bookmark(dir, &bookmark)?;
+                edit::relocate(storage, &bookmark, bookmark.clone().with_url(url))?;
             }
         }
-        Some(4) => {
-            delete_bookmark(dir, &bookmark)?;
+        Some(Action::Delete) => {
+            storage.delete(&bookmark)?;
             let url: String = bookmark.url().to_string();
-            writeln!(streams.ui(), "Deleted bookmark {}", url)?;
+            // A personal copy of a bookmark of a read-only source is deleted, but the bookmark of
+            // the source is still there
+            match storage.source(&bookmark.url())? {
+                Some(source) => {
+                    let msg = format!(
+                        "Deleted personal copy of {}, which remains in read-only source {}",
+                        url, source
+                    );
+                    writeln!(streams.ui(), "{}", msg)?;
+                }
+                None => writeln!(streams.ui(), "Deleted bookmark {}", url)?,
+            }
         }
-        _ => {}
+        Some(Action::Exit) | None => {}
     };
 
     Ok(())
 }
 
-fn score(v0: &HashSet<Tag>, v1: &HashSet<Tag>) -> f64 {
-    let union: f64 = v0.union(v1).count() as f64;
-    let intersection: f64 = v0.intersection(v1).count() as f64;
-    intersection / union
-}
-
+/// Add a bookmark, where the tags and title are prompted for unless `no_input` is set or the
+/// terminal is not interactive, in which case the given tags and title are used as they are
+#[allow(clippy::too_many_arguments)]
 pub fn add(
     mut streams: Streams,
-    dir: &Path,
+    storage: &dyn Storage,
     url: String,
     default: impl TagHolder,
+    title: Option<String>,
+    rules: &Rules,
+    fetch_title: bool,
+    no_input: bool,
     theme: &dyn Theme,
 ) -> Result<(), Error> {
-    let url: String = if PROTOCOL_PREFIX.is_match(&url) { url } else { format!("https://{}", url) };
-    let url = url::Url::parse(&url).unwrap();
-    let title: JoinHandle<Option<String>> = load_title(&url);
-    let tags: HashSet<Tag> = io::read_tags(default, theme, streams.term());
-    let loaded_title: Option<String> = title.join().unwrap_or_default();
-    let title: Option<String> = io::read_title(loaded_title, theme, streams.term());
+    let url: Url = parse_url(&url)?;
+    let interactive: bool = !no_input && streams.term().is_term();
+    let loaded_title: Option<JoinHandle<Option<String>>> =
+        (title.is_none() && fetch_title).then(|| load_title(&url));
+    let default: HashSet<Tag> = default.tags().into_iter().chain(rules.tags(&url)).collect();
+
+    let tags: HashSet<Tag> = if interactive {
+        let bookmarks: Vec<(PathBuf, Bookmark)> = storage.load_all()?;
+        let known: HashMap<Tag, usize> =
+            crate::tag::count(bookmarks.iter().map(|(_, bkm)| bkm.tags()));
+        let suggest = |tags: &HashSet<Tag>| {
+            suggest::suggest(bookmarks.iter().map(|(_, bkm)| bkm), &url, tags)
+        };
+        io::read_tags(default, &known, &suggest, theme, streams.term())
+    } else {
+        default.into_iter().map(Tag::canonical).collect()
+    };
+
+    let title: Option<String> = match title {
+        Some(title) => Some(title),
+        None => {
+            let stored_title: Option<String> = storage.get(&url)?.and_then(|bkm| bkm.title());
+            let loaded_title: Option<String> =
+                loaded_title.and_then(|t| t.join().unwrap_or_default()).or(stored_title);
+            if interactive {
+                io::read_title(loaded_title, theme, streams.term())
+            } else {
+                loaded_title
+            }
+        }
+    };
 
     let bkm = bookmark::Bookmark::new(url, title, tags).unwrap();
-    let bkm: Bookmark = save_bookmark(dir, bkm, true)?;
+    let bkm: Bookmark = storage.save(bkm, true)?;
 
     writeln!(streams.output(), "{}", bkm)?;
 
     Ok(())
 }
 
-fn load_title(url: &Url) -> JoinHandle<Option<String>> {
-    let url = url.clone();
-    thread::spawn(move || {
-        let body: String = reqwest::blocking::get(url).unwrap().text().unwrap();
-        let title: String = TITLE.find(&body).map(|title| title.as_str().to_string())?;
-        let title = title
-            .chars()
-            .skip(7)
-            .take_while(|c| *c != '<')
-            .collect::<String>()
-            .trim()
-            .to_string();
-
-        Some(title)
-    })
+/// Change the title and tags of an existing bookmark without any prompts
+pub fn set(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    url: String,
+    title: Option<String>,
+    add_tags: Vec<Tag>,
+    remove_tags: Vec<Tag>,
+) -> Result<(), Error> {
+    let url: Url = parse_url(&url)?;
+    let bkm: Bookmark = match storage.get(&url)? {
+        Some(bkm) => bkm,
+        None => {
+            writeln!(streams.ui(), "No bookmark with URL {}", url)?;
+            return Err(Error::NotExistingFile);
+        }
+    };
+    if let Some(source) = storage.source(&url)? {
+        let msg = format!("Bookmark is from read-only source {}, saving a personal copy", source);
+        writeln!(streams.ui(), "{}", msg)?;
+    }
+
+    let remove_tags: HashSet<Tag> = removed_tags(remove_tags);
+    let tags: HashSet<Tag> = bkm
+        .tags()
+        .iter()
+        .filter(|tag| !remove_tags.contains(tag))
+        .cloned()
+        .chain(add_tags.into_iter().map(Tag::canonical))
+        .collect();
+    let changed: Bookmark = match title {
+        Some(title) => bkm.clone().with_title(Some(title).filter(|t| !t.is_empty())),
+        None => bkm.clone(),
+    };
+
+    let bkm: Bookmark = save_changes(&mut streams, storage, &bkm, changed.with_tags(tags))?;
+    writeln!(streams.output(), "{}", bkm)?;
+
+    Ok(())
 }
 
-fn save_bookmark(dir: &Path, bkm: Bookmark, merge: bool) -> Result<Bookmark, FileError> {
-    let full_path = dir.join(bkm.rel_path());
-    std::fs::create_dir_all(full_path.parent().expect("Create full path"))?;
+/// The bookmark to save so that `merged`, which may include tags and other fields of read-only
+/// sources, becomes `changed`. Only the fields that differ between the two are changed, in the
+/// bookmark of the storage itself or in a personal copy of `merged` if there is none, so that
+/// nothing from a source is copied into it needlessly. Returns the bookmark to save, or `None` if
+/// nothing would change, and the tags that are kept since they come from a read-only source.
+pub(super) fn own_changes(
+    storage: &dyn Storage,
+    merged: &Bookmark,
+    changed: Bookmark,
+) -> Result<(Option<Bookmark>, HashSet<Tag>), Error> {
+    let url: Url = merged.url();
+    let own: Bookmark = storage.get_own(&url)?.unwrap_or_else(|| merged.clone());
+    let sourced: HashSet<Tag> =
+        storage.get_sourced(&url)?.map(|bkm| bkm.tags().clone()).unwrap_or_default();
 
-    let bkm: Bookmark = if full_path.exists() && merge {
-        Bookmark::from_file(&full_path).map(|prior_bkm| bkm.merge(prior_bkm))?
-    } else {
-        bkm
+    let tags: HashSet<Tag> = own
+        .tags()
+        .intersection(changed.tags())
+        .chain(changed.tags().difference(merged.tags()))
+        .cloned()
+        .collect();
+    let kept: HashSet<Tag> = sourced.difference(changed.tags()).cloned().collect();
+    let pick = |own: Option<String>, merged: Option<String>, changed: Option<String>| {
+        if changed != merged {
+            changed
+        } else {
+            own
+        }
     };
+    let saved: Bookmark = own
+        .clone()
+        .with_tags(tags)
+        .with_title(pick(own.title(), merged.title(), changed.title()))
+        .with_description(pick(own.description(), merged.description(), changed.description()))
+        .with_notes(pick(own.notes(), merged.notes(), changed.notes()));
+
+    // Nothing changes if the only change is the removal of tags that are kept anyway
+    let visible: HashSet<Tag> = changed.tags().union(&kept).cloned().collect();
+    let unchanged: bool = &visible == merged.tags()
+        && changed.title() == merged.title()
+        && changed.description() == merged.description()
+        && changed.notes() == merged.notes();
+
+    Ok((Some(saved).filter(|_| !unchanged), kept))
+}
+
+/// Report tags that can not be removed from the bookmark with the URL, since they come from a
+/// read-only source
+pub(super) fn report_kept(
+    streams: &mut Streams,
+    url: &Url,
+    kept: &HashSet<Tag>,
+) -> Result<(), Error> {
+    if !kept.is_empty() {
+        let tags: String = kept.iter().sorted().join(" ");
+        let msg = format!("Keeping tags {} of {} from a read-only source", tags, url);
+        writeln!(streams.ui(), "{}", msg)?;
+    }
+    Ok(())
+}
+
+/// Save the changes from `merged` to `changed` as described by [own_changes], and return the
+/// bookmark as it is read after the changes
+pub(super) fn save_changes(
+    streams: &mut Streams,
+    storage: &dyn Storage,
+    merged: &Bookmark,
+    changed: Bookmark,
+) -> Result<Bookmark, Error> {
+    let (saved, kept) = own_changes(storage, merged, changed)?;
+    report_kept(streams, &merged.url(), &kept)?;
+    if let Some(saved) = saved {
+        storage.save(saved, false)?;
+    }
+    Ok(storage.get(&merged.url())?.unwrap_or_else(|| merged.clone()))
+}
 
-    let yaml: String = serde_yaml::to_string(&bkm).map_err(|_| FileError::Serialize)?;
-    std::fs::write(full_path, yaml)?;
+/// Tags to remove from bookmarks for the tags given by the user, which are both the tags as they are
+/// given and their canonical tags, so that a stored alias can be removed as well
+pub(super) fn removed_tags(tags: Vec<Tag>) -> HashSet<Tag> {
+    tags.into_iter().flat_map(|tag| [tag.clone(), tag.canonical()]).collect()
+}
+
+/// Parse a URL given by the user, where HTTPS is assumed unless another protocol is given
+pub(super) fn parse_url(url: &str) -> Result<Url, Error> {
+    let url: String = if PROTOCOL_PREFIX.is_match(url) {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    };
+    Url::parse(&url).map_err(|_| Error::InvalidUrl(url))
+}
 
-    Ok(bkm)
+fn load_title(url: &Url) -> JoinHandle<Option<String>> {
+    let url = url.clone();
+    thread::spawn(move || fetch_title(&url))
 }
 
-fn delete_bookmark(dir: &Path, bkm: &Bookmark) -> Result<(), std::io::Error> {
-    let full_path = dir.join(bkm.rel_path());
-    std::fs::remove_file(full_path)
+/// Fetch the web page of the URL and extract its title
+pub(super) fn fetch_title(url: &Url) -> Option<String> {
+    let body: String = match reqwest::blocking::get(url.clone()).and_then(|r| r.text()) {
+        Ok(body) => body,
+        Err(e) => {
+            log::warn!("Unable to fetch title: {}", e);
+            return None;
+        }
+    };
+    let title: String = TITLE.find(&body).map(|title| title.as_str().to_string())?;
+    let title = title
+        .chars()
+        .skip(7)
+        .take_while(|c| *c != '<')
+        .collect::<String>()
+        .trim()
+        .to_string();
+
+    Some(title)
 }
 
 fn format_list_line(
     fields: &[FormatField],
     bkm: &Bookmark,
     path: &Path,
+    source: Option<&str>,
     delimiter: &str,
 ) -> String {
     if fields.is_empty() {
@@ -330,58 +848,268 @@ fn format_list_line(
             FormatField::Title => bkm.title().unwrap_or_default(),
             FormatField::Tags => bkm.tags().iter().sorted().join(" "),
             FormatField::Path => path.display().to_string(),
+            FormatField::Source => source.unwrap_or(PERSONAL_SOURCE).to_string(),
+            FormatField::Created => bkm.created().map(time::format).unwrap_or_default(),
+            FormatField::Modified => bkm.modified().map(time::format).unwrap_or_default(),
+            FormatField::Description => escape(&bkm.description().unwrap_or_default()),
+            FormatField::Notes => escape(&bkm.notes().unwrap_or_default()),
         })
         .join(delimiter)
 }
 
-fn has_all_tags(bkm: &Bookmark, tags: &[Tag]) -> bool {
-    tags.iter().all(|t| bkm.tags().contains(t))
+/// Escape backslashes and line breaks, so that a field with several lines fits on one line
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn has_all_tags(bkm: &Bookmark, query: &Query) -> bool {
+    query.matches(bkm)
 }
 
 pub fn list(
     mut streams: Streams,
-    dir: &Path,
-    tags: Vec<Tag>,
+    storage: &dyn Storage,
+    keywords: Vec<String>,
+    period: Period,
     format: Vec<FormatField>,
     delimiter: String,
 ) -> Result<(), Error> {
-    walkdir::WalkDir::new(dir)
+    let query: Query = Query::parse(&keywords)?;
+    let with_source: bool = format.contains(&FormatField::Source);
+    let bookmarks = storage
+        .load_all()?
+        .into_iter()
+        .filter(|(_, bkm)| has_all_tags(bkm, &query) && period.contains(bkm));
+    for (path, bkm) in bookmarks {
+        let source: Option<&str> = if with_source { storage.source(&bkm.url())? } else { None };
+        let line: String = format_list_line(&format, &bkm, &path, source, &delimiter);
+        writeln!(streams.output(), "{}", line)?;
+    }
+    Ok(())
+}
+
+pub fn config(mut streams: Streams, settings: &Settings) -> Result<(), Error> {
+    write!(streams.output(), "{}", serde_yaml::to_string(settings)?)?;
+    Ok(())
+}
+
+pub fn reindex(mut streams: Streams, dir: &Path, kind: Kind) -> Result<(), Error> {
+    if kind != Kind::Yaml {
+        writeln!(streams.ui(), "Only the yaml storage has an index, nothing to rebuild")?;
+        return Ok(());
+    }
+    let index: Index = Index::rebuild(dir)?;
+    writeln!(streams.ui(), "Indexed {} bookmarks", index.len())?;
+    Ok(())
+}
+
+/// Rewrite all bookmark files in the directory in canonical form, or only list them if `check` is
+/// set. Files that can not be read, or that have keys which would be lost by rewriting them, are
+/// reported and left unchanged. Returns whether all files are in canonical form, or were all
+/// rewritten unless `check` is set.
+pub fn fmt(mut streams: Streams, dir: &Path, kind: Kind, check: bool) -> Result<bool, Error> {
+    if kind != Kind::Yaml {
+        writeln!(streams.ui(), "Only the yaml storage has bookmark files, nothing to format")?;
+        return Ok(true);
+    }
+    let files = walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
         .into_iter()
-        .filter_entry(|f| !is_hidden(f))
+        .filter_entry(|f| f.depth() == 0 || !is_hidden(f))
         .filter_map(|f| f.ok())
         .filter(|f| f.file_type().is_file())
-        .filter_map(|f| {
-            let path: PathBuf = f.into_path();
-            match Bookmark::from_file(&path) {
-                Ok(bkm) => Some((path, bkm)),
-                Err(e) => {
-                    log::error!("Unable to read {}: {}", path.to_str().unwrap_or_default(), e);
-                    None
-                }
+        .filter(|f| f.path().extension().map(|ext| ext == "yaml").unwrap_or(false));
+
+    let mut changed: usize = 0;
+    let mut failed: usize = 0;
+    for file in files {
+        let path: &Path = file.path();
+        let bkm: Bookmark = match Bookmark::from_file(path) {
+            Ok(bkm) => bkm,
+            Err(e) => {
+                writeln!(streams.ui(), "Unable to read {}: {}", path.display(), e)?;
+                failed += 1;
+                continue;
             }
-        })
-        .filter(|(_, bkm)| has_all_tags(bkm, &tags))
-        .try_for_each(|(path, bkm)| {
-            writeln!(streams.output(), "{}", format_list_line(&format, &bkm, &path, &delimiter))
-        })?;
-    Ok(())
+        };
+        let text: String = std::fs::read_to_string(path)?;
+        let unknown: Vec<String> = Bookmark::unknown_keys(&text)?;
+        if !unknown.is_empty() {
+            let keys: String = unknown.join(", ");
+            writeln!(streams.ui(), "Not formatting {} with unknown keys {}", path.display(), keys)?;
+            failed += 1;
+            continue;
+        }
+        let yaml: String = bkm.to_yaml()?;
+        if text == yaml {
+            continue;
+        }
+        writeln!(streams.output(), "{}", path.display())?;
+        if !check {
+            std::fs::write(path, yaml)?;
+        }
+        changed += 1;
+    }
+
+    match (check, changed) {
+        (_, 0) if failed == 0 => writeln!(streams.ui(), "All bookmark files are formatted")?,
+        (_, 0) => {}
+        (true, n) => writeln!(streams.ui(), "{} bookmark file(s) are not formatted", n)?,
+        (false, n) => writeln!(streams.ui(), "Formatted {} bookmark file(s)", n)?,
+    }
+    if failed > 0 {
+        writeln!(streams.ui(), "{} bookmark file(s) could not be formatted", failed)?;
+    }
+
+    Ok(failed == 0 && (changed == 0 || !check))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bookmark::{make_bookmark, temp_dir};
+    use crate::storage::{self, YamlStorage};
+    use std::collections::BTreeMap;
+
+    fn tags(tags: &[&str]) -> HashSet<Tag> {
+        tags.iter().filter_map(|t| Tag::new(*t).ok()).collect()
+    }
+
+    /// A personal collection overlaid with a read-only source, which both have the bookmark
+    /// `https://example.com`
+    fn layered(name: &str) -> Box<dyn Storage> {
+        let personal: PathBuf = temp_dir(&format!("cmd-{}-personal", name));
+        let team: PathBuf = temp_dir(&format!("cmd-{}-team", name));
+        YamlStorage::new(&personal)
+            .write(&make_bookmark("https://example.com", Some("Mine"), &["rust", "old"]))
+            .unwrap();
+        YamlStorage::new(&team)
+            .write(&make_bookmark("https://example.com", Some("Theirs"), &["old", "docs"]))
+            .unwrap();
+        let sources = BTreeMap::from([(String::from("team"), team)]);
+        storage::with_sources(Box::new(YamlStorage::new(&personal)), &personal, sources)
+    }
+
+    #[test]
+    fn fmt_keeps_files_with_unknown_keys() {
+        let dir: PathBuf = temp_dir("cmd-fmt");
+        std::fs::create_dir_all(&dir).unwrap();
+        let unknown: PathBuf = dir.join("unknown.yaml");
+        let yaml = "rating: 5\nurl: https://example.com/\ntitle: null\ntags: []\n";
+        std::fs::write(&unknown, yaml).unwrap();
+        let unformatted: PathBuf = dir.join("unformatted.yaml");
+        std::fs::write(&unformatted, "tags: []\nurl: https://example.org/\n").unwrap();
+
+        assert!(!fmt(Streams::new(), &dir, Kind::Yaml, true).unwrap());
+        assert!(!fmt(Streams::new(), &dir, Kind::Yaml, false).unwrap());
+        assert_eq!(std::fs::read_to_string(&unknown).unwrap(), yaml);
+        let formatted = "url: https://example.org/\ntitle: null\ntags: []\n";
+        assert_eq!(std::fs::read_to_string(&unformatted).unwrap(), formatted);
+    }
 
-    fn make_bookmark(url: &str, title: Option<&str>, tags: &[&str]) -> Bookmark {
-        let url: url::Url = url::Url::parse(url).unwrap();
-        let tags: HashSet<Tag> = tags.iter().filter_map(|t| Tag::new(*t).ok()).collect();
-        Bookmark::new(url, title.map(String::from), tags).unwrap()
+    #[test]
+    fn add_without_input_keeps_existing_title() {
+        let storage = YamlStorage::new(&temp_dir("cmd-add"));
+        let theme = dialoguer::theme::SimpleTheme;
+        let rules = Rules::new(&[]).unwrap();
+        let url = Url::parse("https://github.com").unwrap();
+        storage
+            .write(&make_bookmark("https://github.com", Some("GitHub"), &["git"]))
+            .unwrap();
+
+        let added: Vec<Tag> = vec![Tag::new("code").unwrap()];
+        add(
+            Streams::new(),
+            &storage,
+            "github.com".into(),
+            added,
+            None,
+            &rules,
+            false,
+            true,
+            &theme,
+        )
+        .unwrap();
+        let bkm: Bookmark = storage.get(&url).unwrap().unwrap();
+        assert_eq!(bkm.title().as_deref(), Some("GitHub"));
+        assert_eq!(bkm.tags(), &tags(&["git", "code"]));
+    }
+
+    #[test]
+    fn set_changes_only_given_title_and_tags() {
+        let storage = YamlStorage::new(&temp_dir("cmd-set"));
+        let url = Url::parse("https://github.com").unwrap();
+        let bkm = make_bookmark("https://github.com", Some("GitHub"), &["git", "vcs"]);
+        storage
+            .write(&bkm.with_description(Some(String::from("Code hosting"))))
+            .unwrap();
+
+        let add_tags: Vec<Tag> = vec![Tag::new("code").unwrap()];
+        let remove_tags: Vec<Tag> = vec![Tag::new("vcs").unwrap()];
+        set(Streams::new(), &storage, "github.com".into(), None, add_tags, remove_tags).unwrap();
+        let bkm: Bookmark = storage.get(&url).unwrap().unwrap();
+        assert_eq!(bkm.title().as_deref(), Some("GitHub"));
+        assert_eq!(bkm.description().as_deref(), Some("Code hosting"));
+        assert_eq!(bkm.tags(), &tags(&["git", "code"]));
+
+        set(
+            Streams::new(),
+            &storage,
+            "github.com".into(),
+            Some(String::new()),
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(storage.get(&url).unwrap().unwrap().title(), None);
+        assert!(set(Streams::new(), &storage, "gitlab.com".into(), None, vec![], vec![]).is_err());
+    }
+
+    #[test]
+    fn set_removes_stored_alias() {
+        crate::tag::set_test_aliases();
+        let storage = YamlStorage::new(&temp_dir("cmd-set-alias"));
+        let url = Url::parse("https://kubernetes.io").unwrap();
+        storage
+            .write(&make_bookmark("https://kubernetes.io", None, &["k8s-test", "docs"]))
+            .unwrap();
+
+        let remove_tags: Vec<Tag> = vec![Tag::new("k8s-test").unwrap()];
+        set(Streams::new(), &storage, "kubernetes.io".into(), None, vec![], remove_tags).unwrap();
+        assert_eq!(storage.get(&url).unwrap().unwrap().tags(), &tags(&["docs"]));
+    }
+
+    #[test]
+    fn own_changes_keep_tags_of_sources() {
+        let storage: Box<dyn Storage> = layered("own-changes");
+        let url = Url::parse("https://example.com").unwrap();
+        let merged: Bookmark = storage.get(&url).unwrap().unwrap();
+        let changed: Bookmark = merged.clone().with_tags(tags(&["rust", "docs", "web"]));
+
+        let (saved, kept) = own_changes(&*storage, &merged, changed).unwrap();
+        let saved: Bookmark = saved.unwrap();
+        assert_eq!(saved, make_bookmark("https://example.com", None, &["rust", "web"]));
+        assert_eq!(saved.title(), Some(String::from("Mine")));
+        assert_eq!(kept, tags(&["old"]));
+    }
+
+    #[test]
+    fn own_changes_are_none_when_only_source_tags_are_removed() {
+        let storage: Box<dyn Storage> = layered("only-source");
+        let url = Url::parse("https://example.com").unwrap();
+        let merged: Bookmark = storage.get(&url).unwrap().unwrap();
+        let changed: Bookmark = merged.clone().with_tags(tags(&["rust", "old"]));
+
+        let (saved, kept) = own_changes(&*storage, &merged, changed).unwrap();
+        assert_eq!(saved, None);
+        assert_eq!(kept, tags(&["docs"]));
     }
 
     #[test]
     fn format_default_empty_fields_returns_url_only() {
         let bkm: Bookmark = make_bookmark("https://example.com", Some("Title"), &["rust"]);
         let path: &Path = Path::new("/data/goto/example.com/abc.yaml");
-        let line: String = format_list_line(&[], &bkm, path, "|");
+        let line: String = format_list_line(&[], &bkm, path, None, "|");
         assert_eq!(line, "https://example.com/");
     }
 
@@ -389,7 +1117,7 @@ mod tests {
     fn format_url_field_returns_url() {
         let bkm: Bookmark = make_bookmark("https://example.com", None, &[]);
         let path: &Path = Path::new("/data/goto/example.com/abc.yaml");
-        let line: String = format_list_line(&[FormatField::Url], &bkm, path, "|");
+        let line: String = format_list_line(&[FormatField::Url], &bkm, path, None, "|");
         assert_eq!(line, "https://example.com/");
     }
 
@@ -402,6 +1130,7 @@ mod tests {
             &[FormatField::Url, FormatField::Title, FormatField::Tags],
             &bkm,
             path,
+            None,
             "|",
         );
         assert_eq!(line, "https://example.com/|My Site|docs rust");
@@ -415,6 +1144,7 @@ mod tests {
             &[FormatField::Url, FormatField::Title, FormatField::Tags],
             &bkm,
             path,
+            None,
             "|",
         );
         assert_eq!(line, "https://example.com/||rust");
@@ -424,7 +1154,7 @@ mod tests {
     fn format_path_field_returns_absolute_path() {
         let bkm: Bookmark = make_bookmark("https://example.com", None, &[]);
         let path: &Path = Path::new("/data/goto/example.com/abc.yaml");
-        let line: String = format_list_line(&[FormatField::Path], &bkm, path, "|");
+        let line: String = format_list_line(&[FormatField::Path], &bkm, path, None, "|");
         assert_eq!(line, "/data/goto/example.com/abc.yaml");
     }
 
@@ -433,7 +1163,7 @@ mod tests {
         let bkm: Bookmark = make_bookmark("https://example.com", Some("T"), &["z", "a"]);
         let path: &Path = Path::new("/p/x.yaml");
         let line: String =
-            format_list_line(&[FormatField::Tags, FormatField::Url], &bkm, path, "|");
+            format_list_line(&[FormatField::Tags, FormatField::Url], &bkm, path, None, "|");
         assert_eq!(line, "a z|https://example.com/");
     }
 
@@ -442,28 +1172,38 @@ mod tests {
         let bkm: Bookmark = make_bookmark("https://example.com", Some("T"), &[]);
         let path: &Path = Path::new("/p/x.yaml");
         let line: String =
-            format_list_line(&[FormatField::Url, FormatField::Title], &bkm, path, " | ");
+            format_list_line(&[FormatField::Url, FormatField::Title], &bkm, path, None, " | ");
         assert_eq!(line, "https://example.com/ | T");
     }
 
+    #[test]
+    fn format_notes_escapes_newlines() {
+        let bkm: Bookmark = make_bookmark("https://example.com", None, &[])
+            .with_notes(Some(String::from("use staging creds\nfrom C:\\vault")));
+        let path: &Path = Path::new("/p/x.yaml");
+        let line: String =
+            format_list_line(&[FormatField::Url, FormatField::Notes], &bkm, path, None, "|");
+        assert_eq!(line, "https://example.com/|use staging creds\\nfrom C:\\\\vault");
+    }
+
     #[test]
     fn has_all_tags_returns_true_when_all_present() {
         let bkm: Bookmark = make_bookmark("https://example.com", None, &["rust", "docs", "async"]);
-        let required: Vec<Tag> = vec![Tag::new("rust").unwrap(), Tag::new("docs").unwrap()];
+        let required: Query = Query::parse(&["rust", "docs"]).unwrap();
         assert!(has_all_tags(&bkm, &required));
     }
 
     #[test]
     fn has_all_tags_returns_false_when_tag_missing() {
         let bkm: Bookmark = make_bookmark("https://example.com", None, &["rust"]);
-        let required: Vec<Tag> = vec![Tag::new("rust").unwrap(), Tag::new("docs").unwrap()];
+        let required: Query = Query::parse(&["rust", "docs"]).unwrap();
         assert!(!has_all_tags(&bkm, &required));
     }
 
     #[test]
     fn has_all_tags_returns_true_when_no_tags_required() {
         let bkm: Bookmark = make_bookmark("https://example.com", None, &["rust"]);
-        let required: Vec<Tag> = vec![];
+        let required: Query = Query::parse::<&str>(&[]).unwrap();
         assert!(has_all_tags(&bkm, &required));
     }
 }
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use dialoguer::{theme::Theme, Confirm};
+use itertools::Itertools;
+use url::Url;
+
+use super::import::load_titles;
+use super::{has_all_tags, own_changes, removed_tags, report_kept};
+use crate::bookmark::Bookmark;
+use crate::io::Streams;
+use crate::query::Query;
+use crate::storage::Storage;
+use crate::tag::Tag;
+use crate::Error;
+
+/// Change the tags and titles of all bookmarks that match the query, after showing the changes and
+/// asking for confirmation unless `yes` is set. Tags that come from a read-only source are kept,
+/// since they can not be removed.
+#[allow(clippy::too_many_arguments)]
+pub fn bulk(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    query: Vec<String>,
+    add_tags: Vec<Tag>,
+    remove_tags: Vec<Tag>,
+    title_from_page: bool,
+    dry_run: bool,
+    yes: bool,
+    theme: &dyn Theme,
+) -> Result<(), Error> {
+    if add_tags.is_empty() && remove_tags.is_empty() && !title_from_page {
+        writeln!(streams.ui(), "No changes given, see --help")?;
+        return Ok(());
+    }
+
+    let query: Query = Query::parse(&query)?;
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    for (_, bkm) in storage.load_all()?.into_iter().filter(|(_, bkm)| has_all_tags(bkm, &query)) {
+        match storage.source(&bkm.url())? {
+            Some(source) => {
+                let msg = format!("Skipping {} from read-only source {}", bkm.url(), source);
+                writeln!(streams.ui(), "{}", msg)?;
+            }
+            None => bookmarks.push(bkm),
+        }
+    }
+
+    let titles: Vec<Option<String>> = if title_from_page {
+        let urls: Vec<Url> = bookmarks.iter().map(|bkm| bkm.url()).collect();
+        load_titles(&urls.iter().collect_vec())
+    } else {
+        vec![None; bookmarks.len()]
+    };
+
+    let add_tags: HashSet<Tag> = add_tags.into_iter().map(Tag::canonical).collect();
+    let remove_tags: HashSet<Tag> = removed_tags(remove_tags);
+    let mut changes: Vec<Bookmark> = Vec::new();
+    for (bkm, title) in bookmarks.into_iter().zip(titles) {
+        let tags: HashSet<Tag> = bkm
+            .tags()
+            .iter()
+            .filter(|tag| !remove_tags.contains(tag))
+            .chain(&add_tags)
+            .cloned()
+            .collect();
+        let title: Option<String> = title.or_else(|| bkm.title());
+        let changed: Bookmark = bkm.clone().with_tags(tags.clone()).with_title(title.clone());
+        let (saved, kept) = own_changes(storage, &bkm, changed)?;
+        report_kept(&mut streams, &bkm.url(), &kept)?;
+        if let Some(saved) = saved {
+            let tags: HashSet<Tag> = tags.union(&kept).cloned().collect();
+            writeln!(streams.output(), "{}", describe(&bkm, &tags, &title))?;
+            changes.push(saved);
+        }
+    }
+
+    if changes.is_empty() {
+        writeln!(streams.ui(), "No bookmarks to change")?;
+        return Ok(());
+    }
+    if dry_run {
+        writeln!(streams.ui(), "Would change {} bookmark(s)", changes.len())?;
+        return Ok(());
+    }
+    if !yes {
+        if !streams.term().is_term() {
+            let msg =
+                "Not changing any bookmarks without --yes, since the terminal is not interactive";
+            writeln!(streams.ui(), "{}", msg)?;
+            return Ok(());
+        }
+        streams.flush_all()?;
+        let confirmed: bool = Confirm::with_theme(theme)
+            .with_prompt(format!("Change {} bookmark(s)?", changes.len()))
+            .default(false)
+            .interact_on(streams.term())?;
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    let changed: usize = changes.len();
+    for bkm in changes {
+        storage.save(bkm, false)?;
+    }
+    writeln!(streams.ui(), "Changed {} bookmark(s)", changed)?;
+
+    Ok(())
+}
+
+/// Describe the changes of a bookmark, such as
+///
+/// ```text
+/// https://github.com/
+///   tags: git vcs -> code git
+///   title: GitHub -> GitHub: Let's build from here
+/// ```
+fn describe(bkm: &Bookmark, tags: &HashSet<Tag>, title: &Option<String>) -> String {
+    let mut lines: Vec<String> = vec![bkm.url().to_string()];
+    if tags != bkm.tags() {
+        let before: String = bkm.tags().iter().sorted().join(" ");
+        let after: String = tags.iter().sorted().join(" ");
+        lines.push(format!("  tags: {} -> {}", before, after));
+    }
+    if title != &bkm.title() {
+        let before: String = bkm.title().unwrap_or_default();
+        lines.push(format!("  title: {} -> {}", before, title.clone().unwrap_or_default()));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::{make_bookmark, temp_dir};
+    use crate::storage::YamlStorage;
+
+    #[test]
+    fn bulk_removes_stored_alias() {
+        crate::tag::set_test_aliases();
+        let storage = YamlStorage::new(&temp_dir("bulk-alias"));
+        let url = Url::parse("https://kubernetes.io").unwrap();
+        storage
+            .write(&make_bookmark("https://kubernetes.io", None, &["k8s-test", "docs"]))
+            .unwrap();
+
+        let theme = dialoguer::theme::SimpleTheme;
+        let query: Vec<String> = vec![String::from("k8s-test")];
+        let remove_tags: Vec<Tag> = vec![Tag::new("k8s-test").unwrap()];
+        bulk(Streams::new(), &storage, query, vec![], remove_tags, false, false, true, &theme)
+            .unwrap();
+        let expected: HashSet<Tag> = HashSet::from([Tag::new("docs").unwrap()]);
+        assert_eq!(storage.get(&url).unwrap().unwrap().tags(), &expected);
+    }
+
+    #[test]
+    fn describe_shows_only_changed_fields() {
+        let url = Url::parse("https://github.com/").unwrap();
+        let tags: HashSet<Tag> = ["git", "vcs"].iter().map(|t| Tag::new(*t).unwrap()).collect();
+        let bkm = Bookmark::new(url, Some(String::from("GitHub")), tags.clone()).unwrap();
+        let title = Some(String::from("GitHub: Let's build from here"));
+        let expected = "https://github.com/\n  title: GitHub -> GitHub: Let's build from here";
+        assert_eq!(describe(&bkm, &tags, &title), expected);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::str::FromStr;
+
+use dialoguer::{theme::Theme, Confirm, Editor, FuzzySelect};
+
+use super::{filter, save_changes};
+use crate::bookmark::Bookmark;
+use crate::io::Streams;
+use crate::query::Query;
+use crate::storage::Storage;
+use crate::tag::Tag;
+use crate::visits::Visits;
+use crate::Error;
+
+/// Edit the bookmark that matches the keywords in the editor, where the bookmark is selected from
+/// all matching bookmarks if there are several and the terminal is interactive
+pub fn edit(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    visits: &Visits,
+    keywords: Vec<String>,
+    min_score: f64,
+    theme: &dyn Theme,
+) -> Result<(), Error> {
+    let query: Query = Query::parse(&keywords)?;
+    let bookmarks: Vec<Bookmark> = filter(storage, visits, &query, min_score)?
+        .into_iter()
+        .map(|(_, bkm)| bkm)
+        .collect();
+
+    let selection: Option<usize> = match bookmarks.len() {
+        0 => {
+            writeln!(streams.ui(), "No bookmarks found")?;
+            return Ok(());
+        }
+        1 => Some(0),
+        _ if !streams.term().is_term() => Some(0),
+        _ => FuzzySelect::with_theme(theme)
+            .with_prompt("Select bookmark")
+            .default(0)
+            .items(&bookmarks)
+            .interact_on_opt(streams.term())?,
+    };
+
+    match selection {
+        Some(i) => edit_bookmark(streams, storage, bookmarks[i].clone(), theme),
+        None => Ok(()),
+    }
+}
+
+/// Edit the whole bookmark as YAML in the editor given by `$VISUAL` or `$EDITOR`. The bookmark is
+/// edited again if it can not be parsed, and moved if its URL was changed. Edits of a bookmark of
+/// a read-only source are saved to a personal copy, except for its URL, which can not be changed.
+pub(super) fn edit_bookmark(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    bookmark: Bookmark,
+    theme: &dyn Theme,
+) -> Result<(), Error> {
+    let source: Option<&str> = storage.source(&bookmark.url())?;
+    if let Some(source) = source {
+        let msg = format!(
+            "Bookmark is from read-only source {}, saving a personal copy without changing its URL",
+            source
+        );
+        writeln!(streams.ui(), "{}", msg)?;
+        streams.flush_all()?;
+    }
+
+    let original: String = bookmark.to_yaml()?;
+    let mut yaml: String = original.clone();
+    let edited: Bookmark = loop {
+        let text: String = match Editor::new().extension(".yaml").edit(&yaml)? {
+            Some(text) if text.trim() != original.trim() => text,
+            _ => {
+                writeln!(streams.ui(), "Bookmark was not changed")?;
+                return Ok(());
+            }
+        };
+        match Bookmark::from_str(&text) {
+            Ok(bkm) => break bkm,
+            Err(e) => {
+                writeln!(streams.ui(), "Unable to parse bookmark: {}", e)?;
+                if !streams.term().is_term() {
+                    return Err(Error::Serialization);
+                }
+                let again: bool = Confirm::with_theme(theme)
+                    .with_prompt("Edit again?")
+                    .default(true)
+                    .interact_on(streams.term())?;
+                if !again {
+                    return Ok(());
+                }
+                yaml = text;
+            }
+        }
+    };
+
+    // Tags are read from the file as they are written, so they are normalized like entered tags
+    let tags: HashSet<Tag> = edited.tags().iter().filter_map(Tag::renormalized).collect();
+    let edited: Bookmark = edited.with_tags(tags.into_iter().map(Tag::canonical).collect());
+
+    let edited: Bookmark = match source {
+        Some(_) if edited.url() != bookmark.url() => {
+            let msg = format!("Keeping URL {} of bookmark from read-only source", bookmark.url());
+            writeln!(streams.ui(), "{}", msg)?;
+            edited.with_url(bookmark.url())
+        }
+        _ => edited,
+    };
+
+    let saved: Bookmark = if edited.url() != bookmark.url() {
+        relocate(storage, &bookmark, edited)?
+    } else {
+        save_changes(&mut streams, storage, &bookmark, edited)?
+    };
+    writeln!(streams.output(), "{}", saved)?;
+
+    Ok(())
+}
+
+/// Save the bookmark under its new URL, merged with any bookmark that already has that URL, and
+/// delete the bookmark under its old URL
+pub(super) fn relocate(
+    storage: &dyn Storage,
+    old: &Bookmark,
+    new: Bookmark,
+) -> Result<Bookmark, Error> {
+    let saved: Bookmark = storage.save(new, true)?;
+    storage.delete(old)?;
+    Ok(saved)
+}
@@ -0,0 +1,92 @@
+use std::io::Write;
+
+use itertools::Itertools;
+
+use super::{candidates, Candidate};
+use crate::io::Streams;
+use crate::query::Query;
+use crate::rank::KeywordScore;
+use crate::storage::Storage;
+use crate::visits::Visits;
+use crate::Error;
+
+pub fn explain(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    visits: &Visits,
+    keywords: Vec<String>,
+    limit: usize,
+    min_score: f64,
+) -> Result<(), Error> {
+    let query: Query = Query::parse(&keywords)?;
+    let candidates: Vec<Candidate> = candidates(storage, visits, &query, min_score)?;
+
+    if candidates.is_empty() {
+        writeln!(streams.ui(), "No bookmarks found")?;
+        return Ok(());
+    }
+
+    for (i, candidate) in candidates.iter().take(limit).enumerate() {
+        let out = streams.output();
+        let bkm = &candidate.bookmark;
+        match bkm.title() {
+            Some(title) => writeln!(out, "{:>3}. {} ({})", i + 1, bkm.url(), title)?,
+            None => writeln!(out, "{:>3}. {}", i + 1, bkm.url())?,
+        }
+        writeln!(
+            out,
+            "     score {:.3} = relevance {:.3} + frecency {:.3}",
+            candidate.total(),
+            candidate.score.value,
+            candidate.frecency
+        )?;
+
+        let max_score: f64 = candidate.score.keywords.iter().map(|k| k.idf).sum();
+        let width: usize =
+            candidate.score.keywords.iter().map(|k| k.keyword.len()).max().unwrap_or(0);
+        for keyword in &candidate.score.keywords {
+            writeln!(out, "     {}", explain_keyword(keyword, max_score, width))?;
+        }
+
+        if candidate.cut {
+            writeln!(
+                out,
+                "     cut: relevance {:.3} is below minimum score {}",
+                candidate.score.value, min_score
+            )?;
+        }
+    }
+
+    let remaining: usize = candidates.len().saturating_sub(limit);
+    if remaining > 0 {
+        writeln!(streams.ui(), "{} more bookmarks not shown", remaining)?;
+    }
+
+    Ok(())
+}
+
+/// Describe how a keyword matched, such as
+/// `kubernete  weight 1.386  matched kubernetes in tags, title (1 typo, x0.60, tf 3.42)  +0.412`
+fn explain_keyword(keyword: &KeywordScore, max_score: f64, width: usize) -> String {
+    let contribution: f64 = if max_score > 0.0 { keyword.score / max_score } else { 0.0 };
+    let matched: String = match &keyword.term {
+        Some(term) => format!(
+            "matched {} in {} ({}, x{:.2}, tf {:.2})",
+            term.term,
+            term.fields.iter().join(", "),
+            term.kind,
+            term.kind.factor(),
+            term.tf
+        ),
+        None => String::from("no match"),
+    };
+
+    format!(
+        "{:width$}  weight {:.3}  {}  +{:.3}",
+        keyword.keyword,
+        keyword.idf,
+        matched,
+        contribution,
+        width = width
+    )
+}
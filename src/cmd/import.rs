@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use url::Url;
+
+use super::{fetch_title, parse_url};
+use crate::bookmark::Bookmark;
+use crate::io::Streams;
+use crate::rules::Rules;
+use crate::storage::Storage;
+use crate::tag::Tag;
+use crate::Error;
+
+/// Most web pages that are fetched at the same time for their titles
+const MAX_WORKERS: usize = 8;
+
+/// A line of input with a bookmark to add
+#[derive(Debug, PartialEq)]
+struct Entry {
+    url: Url,
+    tags: HashSet<Tag>,
+}
+
+/// Add bookmarks from a file, or from standard input if no file is given, without any prompts.
+/// Each line holds a URL optionally followed by tags, separated by whitespace. Empty lines and
+/// lines starting with `#` are ignored.
+pub fn add_from(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    file: Option<PathBuf>,
+    rules: &Rules,
+    fetch_titles: bool,
+) -> Result<(), Error> {
+    let lines: Vec<String> = match file {
+        Some(path) => BufReader::new(File::open(path)?).lines().collect::<Result<_, _>>()?,
+        None => std::io::stdin().lock().lines().collect::<Result<_, _>>()?,
+    };
+
+    let mut failed: usize = 0;
+    let mut entries: Vec<Entry> = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        match parse_line(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(e) => {
+                writeln!(streams.ui(), "Line {}: {}", i + 1, e)?;
+                failed += 1;
+            }
+        }
+    }
+
+    let (entries, duplicates): (Vec<Entry>, usize) = deduplicate(entries);
+
+    // Titles of bookmarks that already have one are kept rather than fetched again
+    let mut titles: Vec<Option<String>> = Vec::with_capacity(entries.len());
+    let mut existing: Vec<bool> = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let prior: Option<Bookmark> = storage.get(&entry.url)?;
+        existing.push(prior.is_some());
+        titles.push(prior.and_then(|bkm| bkm.title()));
+    }
+    if fetch_titles {
+        let missing: Vec<&Url> = entries
+            .iter()
+            .zip(&titles)
+            .filter(|(_, t)| t.is_none())
+            .map(|(e, _)| &e.url)
+            .collect();
+        let mut fetched = load_titles(&missing).into_iter();
+        for title in titles.iter_mut().filter(|t| t.is_none()) {
+            *title = fetched.next().flatten();
+        }
+    }
+
+    let (mut added, mut merged): (usize, usize) = (0, duplicates);
+    for ((entry, title), existed) in entries.into_iter().zip(titles).zip(existing) {
+        let tags: HashSet<Tag> = entry.tags.into_iter().chain(rules.tags(&entry.url)).collect();
+        let bkm: Bookmark = Bookmark::new(entry.url.clone(), title, tags).unwrap();
+        match storage.save(bkm, true) {
+            Ok(bkm) => writeln!(streams.output(), "{}", bkm)?,
+            Err(e) => {
+                writeln!(streams.ui(), "Unable to save {}: {}", entry.url, e)?;
+                failed += 1;
+                continue;
+            }
+        }
+        if existed {
+            merged += 1;
+        } else {
+            added += 1;
+        }
+    }
+
+    let msg = format!("Added {}, merged {} and failed {} bookmark(s)", added, merged, failed);
+    writeln!(streams.ui(), "{}", msg)?;
+
+    Ok(())
+}
+
+/// Parse a line such as `github.com git vcs`, or `None` if the line is empty or a comment
+fn parse_line(line: &str) -> Result<Option<Entry>, String> {
+    let mut parts = line.split_whitespace();
+    let url: &str = match parts.next() {
+        Some(url) if !url.starts_with('#') => url,
+        _ => return Ok(None),
+    };
+    let url: Url = parse_url(url).map_err(|_| format!("Invalid URL {}", url))?;
+    let tags: HashSet<Tag> = parts
+        .map(|tag| Tag::new(tag).map(Tag::canonical).map_err(|e| format!("{}: {}", tag, e)))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Some(Entry { url, tags }))
+}
+
+/// Combine entries with the same URL into the first of them, with the tags of all of them, so that
+/// each URL is only looked up, fetched and saved once. Returns the combined entries and the number
+/// of entries that were combined into another.
+fn deduplicate(entries: Vec<Entry>) -> (Vec<Entry>, usize) {
+    let mut unique: Vec<Entry> = Vec::with_capacity(entries.len());
+    let mut positions: HashMap<Url, usize> = HashMap::with_capacity(entries.len());
+    let mut duplicates: usize = 0;
+    for entry in entries {
+        match positions.get(&entry.url) {
+            Some(&i) => {
+                unique[i].tags.extend(entry.tags);
+                duplicates += 1;
+            }
+            None => {
+                positions.insert(entry.url.clone(), unique.len());
+                unique.push(entry);
+            }
+        }
+    }
+
+    (unique, duplicates)
+}
+
+/// Fetch the titles of the URLs, with at most [MAX_WORKERS] pages fetched at the same time
+pub(super) fn load_titles(urls: &[&Url]) -> Vec<Option<String>> {
+    let next = AtomicUsize::new(0);
+    let titles: Vec<Mutex<Option<String>>> = urls.iter().map(|_| Mutex::new(None)).collect();
+    thread::scope(|scope| {
+        for _ in 0..MAX_WORKERS.min(urls.len()) {
+            scope.spawn(|| loop {
+                let i: usize = next.fetch_add(1, Ordering::Relaxed);
+                match urls.get(i) {
+                    Some(url) => *titles[i].lock().unwrap() = fetch_title(url),
+                    None => break,
+                }
+            });
+        }
+    });
+
+    titles.into_iter().map(|title| title.into_inner().unwrap()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_reads_url_and_tags() {
+        let entry: Entry = parse_line("  github.com git vcs ").unwrap().unwrap();
+        assert_eq!(entry.url.as_str(), "https://github.com/");
+        let tags: HashSet<Tag> = ["git", "vcs"].iter().map(|t| Tag::new(*t).unwrap()).collect();
+        assert_eq!(entry.tags, tags);
+    }
+
+    #[test]
+    fn parse_line_skips_empty_lines_and_comments() {
+        assert_eq!(parse_line("   "), Ok(None));
+        assert_eq!(parse_line("# links from chat"), Ok(None));
+    }
+
+    #[test]
+    fn parse_line_rejects_invalid_url() {
+        assert!(parse_line("http://[ rust").is_err());
+    }
+
+    #[test]
+    fn duplicated_urls_are_combined_with_all_tags() {
+        let lines = ["github.com git", "docs.rs rust", "https://github.com/ vcs"];
+        let entries: Vec<Entry> = lines.iter().map(|l| parse_line(l).unwrap().unwrap()).collect();
+        let (entries, duplicates) = deduplicate(entries);
+        assert_eq!(duplicates, 1);
+        let urls: Vec<&str> = entries.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, ["https://github.com/", "https://docs.rs/"]);
+        let tags: HashSet<Tag> = ["git", "vcs"].iter().map(|t| Tag::new(*t).unwrap()).collect();
+        assert_eq!(entries[0].tags, tags);
+    }
+}
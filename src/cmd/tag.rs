@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use itertools::Itertools;
+
+use super::{own_changes, report_kept};
+use crate::bookmark::Bookmark;
+use crate::io::Streams;
+use crate::rules::Rules;
+use crate::storage::Storage;
+use crate::tag::{self, Tag};
+use crate::Error;
+
+#[derive(Debug, Subcommand)]
+pub enum TagCommand {
+    /// List tags
+    ///
+    /// List all tags together with the number of bookmarks that have the tag, with the most used
+    /// tags first.
+    List {
+        /// Show the hierarchy of tags, where the count of a tag includes its descendants
+        #[clap(long)]
+        tree: bool,
+    },
+    /// Rename a tag
+    ///
+    /// Rename a tag on every bookmark that has it. If a bookmark already has the new tag, the old
+    /// tag is simply removed.
+    Rename {
+        from: Tag,
+        to: Tag,
+        /// Only show which bookmarks would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Merge tags into one tag
+    ///
+    /// Replace each of the given tags with a single tag on every bookmark that has any of them,
+    /// such as `goto tag merge golang go-lang --into go`.
+    Merge {
+        #[clap(required = true)]
+        tags: Vec<Tag>,
+        #[clap(long)]
+        into: Tag,
+        /// Only show which bookmarks would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Replace aliases with canonical tags
+    ///
+    /// Replace every tag that is configured as an alias of another tag with that tag, on every
+    /// bookmark that has any alias as a tag.
+    Canonicalize {
+        /// Only show which bookmarks would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Normalize stored tags
+    ///
+    /// Normalize every tag of every bookmark the way new tags are normalized, which is needed for
+    /// tags that were stored by an earlier version of goto. Tags that become the same tag are
+    /// merged, and tags that are no longer valid are removed.
+    Normalize {
+        /// Only show which bookmarks would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Delete a tag
+    ///
+    /// Remove a tag from every bookmark that has it. The bookmarks themselves are kept.
+    Delete {
+        tag: Tag,
+        /// Only show which bookmarks would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+pub fn tag(streams: Streams, storage: &dyn Storage, cmd: TagCommand) -> Result<(), Error> {
+    match cmd {
+        TagCommand::List { tree: false } => list(streams, storage),
+        TagCommand::List { tree: true } => tree(streams, storage),
+        TagCommand::Rename { from, to, dry_run } => {
+            let from: HashSet<Tag> = HashSet::from([from]);
+            let to: Tag = to.canonical();
+            retag_by(streams, storage, |bkm| replaced(bkm.tags(), &from, Some(&to)), dry_run)
+        }
+        TagCommand::Merge {
+            tags,
+            into,
+            dry_run,
+        } => {
+            let from: HashSet<Tag> = tags.into_iter().collect();
+            let into: Tag = into.canonical();
+            retag_by(streams, storage, |bkm| replaced(bkm.tags(), &from, Some(&into)), dry_run)
+        }
+        TagCommand::Canonicalize { dry_run } => {
+            retag_by(streams, storage, |bkm| canonicalized(bkm.tags()), dry_run)
+        }
+        TagCommand::Normalize { dry_run } => {
+            retag_by(streams, storage, |bkm| renormalized(bkm.tags()), dry_run)
+        }
+        TagCommand::Delete { tag, dry_run } => {
+            let from: HashSet<Tag> = HashSet::from([tag]);
+            retag_by(streams, storage, |bkm| replaced(bkm.tags(), &from, None), dry_run)
+        }
+    }
+}
+
+fn list(mut streams: Streams, storage: &dyn Storage) -> Result<(), Error> {
+    let bookmarks: Vec<(PathBuf, Bookmark)> = storage.load_all()?;
+    let counts: HashMap<Tag, usize> = tag::count(bookmarks.iter().map(|(_, bkm)| bkm.tags()));
+
+    let counts = counts.into_iter().sorted_by(|(t0, c0), (t1, c1)| c1.cmp(c0).then(t0.cmp(t1)));
+    for (tag, count) in counts {
+        writeln!(streams.output(), "{:>6} {}", count, tag)?;
+    }
+
+    Ok(())
+}
+
+/// List tags as a tree such as the one below, where each tag is counted for all bookmarks that
+/// have the tag or any of its descendants
+///
+/// ```text
+///      3 lang
+///      2   rust
+///      1     async
+///      1   go
+/// ```
+fn tree(mut streams: Streams, storage: &dyn Storage) -> Result<(), Error> {
+    let mut counts: HashMap<Tag, usize> = HashMap::new();
+    for (_, bkm) in storage.load_all()? {
+        let lineage: HashSet<Tag> = bkm.tags().iter().flat_map(|tag| tag.lineage()).collect();
+        for tag in lineage {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+
+    let counts = counts.into_iter().sorted_by(|(t0, _), (t1, _)| t0.levels().cmp(t1.levels()));
+    for (tag, count) in counts {
+        let depth: usize = tag.levels().count() - 1;
+        let name: &str = tag.levels().next_back().unwrap_or_default();
+        writeln!(streams.output(), "{:>6} {}{}", count, "  ".repeat(depth), name)?;
+    }
+
+    Ok(())
+}
+
+/// Add the tags of all matching rules to every bookmark
+pub fn retag(
+    streams: Streams,
+    storage: &dyn Storage,
+    rules: &Rules,
+    dry_run: bool,
+) -> Result<(), Error> {
+    retag_by(streams, storage, |bkm| added(bkm.tags(), rules.tags(&bkm.url())), dry_run)
+}
+
+/// Change the tags of every bookmark for which `retagged` returns new tags. Tags that come from a
+/// read-only source are kept, since they can not be removed.
+fn retag_by(
+    mut streams: Streams,
+    storage: &dyn Storage,
+    retagged: impl Fn(&Bookmark) -> Option<HashSet<Tag>>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut changed: usize = 0;
+    for (path, bkm) in storage.load_all()? {
+        let tags: HashSet<Tag> = match retagged(&bkm) {
+            Some(tags) => tags,
+            None => continue,
+        };
+
+        if let Some(source) = storage.source(&bkm.url())? {
+            let msg = format!("Skipping {} from read-only source {}", bkm.url(), source);
+            writeln!(streams.ui(), "{}", msg)?;
+            continue;
+        }
+
+        let (saved, kept) = own_changes(storage, &bkm, bkm.clone().with_tags(tags.clone()))?;
+        report_kept(&mut streams, &bkm.url(), &kept)?;
+        let saved: Bookmark = match saved {
+            Some(saved) => saved,
+            None => continue,
+        };
+
+        let tags: HashSet<Tag> = tags.union(&kept).cloned().collect();
+        writeln!(streams.output(), "{}", describe(&path, &bkm, &tags))?;
+        if !dry_run {
+            storage.save(saved, false)?;
+        }
+        changed += 1;
+    }
+
+    match (dry_run, changed) {
+        (_, 0) => writeln!(streams.ui(), "No bookmarks to change")?,
+        (true, n) => writeln!(streams.ui(), "Would change {} bookmark(s)", n)?,
+        (false, n) => writeln!(streams.ui(), "Changed {} bookmark(s)", n)?,
+    }
+
+    Ok(())
+}
+
+/// The tags after replacing the tags `from` with `into`, or `None` if none of the tags are
+/// affected
+fn replaced(tags: &HashSet<Tag>, from: &HashSet<Tag>, into: Option<&Tag>) -> Option<HashSet<Tag>> {
+    if tags.is_disjoint(from) {
+        return None;
+    }
+    let tags: HashSet<Tag> =
+        tags.iter().filter(|tag| !from.contains(tag)).chain(into).cloned().collect();
+
+    Some(tags)
+}
+
+/// The tags after adding the tags `add`, or `None` if all of them are already present
+fn added(tags: &HashSet<Tag>, add: HashSet<Tag>) -> Option<HashSet<Tag>> {
+    if add.is_subset(tags) {
+        None
+    } else {
+        Some(tags.union(&add).cloned().collect())
+    }
+}
+
+/// The tags after replacing aliases with their canonical tags, or `None` if there are no aliases
+/// among the tags
+fn canonicalized(tags: &HashSet<Tag>) -> Option<HashSet<Tag>> {
+    let canonical: HashSet<Tag> = tags.iter().cloned().map(Tag::canonical).collect();
+    if &canonical == tags {
+        None
+    } else {
+        Some(canonical)
+    }
+}
+
+/// The tags after normalizing them again, or `None` if all tags are already normalized
+fn renormalized(tags: &HashSet<Tag>) -> Option<HashSet<Tag>> {
+    let normalized: HashSet<Tag> = tags.iter().filter_map(Tag::renormalized).collect();
+    if &normalized == tags {
+        None
+    } else {
+        Some(normalized)
+    }
+}
+
+/// Describe a change of tags, such as `/data/goto/example.com/a.yaml: golang rust -> go rust`, by
+/// the URL of the bookmark instead of the path if it has no file of its own
+fn describe(path: &Path, bkm: &Bookmark, tags: &HashSet<Tag>) -> String {
+    let name: String = if path.as_os_str().is_empty() {
+        bkm.url().to_string()
+    } else {
+        path.display().to_string()
+    };
+    format!(
+        "{}: {} -> {}",
+        name,
+        bkm.tags().iter().sorted().join(" "),
+        tags.iter().sorted().join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> HashSet<Tag> {
+        tags.iter().filter_map(|t| Tag::new(*t).ok()).collect()
+    }
+
+    #[test]
+    fn replaced_replaces_all_merged_tags() {
+        let into = Tag::new("go").unwrap();
+        let replaced = replaced(
+            &tags(&["golang", "go-lang", "web"]),
+            &tags(&["golang", "go-lang"]),
+            Some(&into),
+        );
+        assert_eq!(replaced, Some(tags(&["go", "web"])));
+    }
+
+    #[test]
+    fn replaced_removes_deleted_tag() {
+        let replaced = replaced(&tags(&["rust", "old"]), &tags(&["old"]), None);
+        assert_eq!(replaced, Some(tags(&["rust"])));
+    }
+
+    #[test]
+    fn renormalized_merges_duplicates() {
+        let stored: HashSet<Tag> =
+            serde_yaml::from_str(r#"["Caf\u00e9", "cafe\u0301", rust]"#).unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(renormalized(&stored), Some(tags(&["caf\u{e9}", "rust"])));
+        assert_eq!(renormalized(&tags(&["rust"])), None);
+    }
+
+    #[test]
+    fn replaced_ignores_bookmark_without_tag() {
+        let into = Tag::new("go").unwrap();
+        assert_eq!(replaced(&tags(&["rust"]), &tags(&["golang"]), Some(&into)), None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt::Display;
+
+/// Relative weight of a term that was matched because the keyword is a prefix of it
+const PREFIX_FACTOR: f64 = 0.75;
+
+/// Relative weight of a term that was matched with an edit distance of one or two respectively
+const TYPO_FACTORS: [f64; 2] = [0.6, 0.4];
+
+/// Relative weight of a tag that was matched because the keyword is one of its ancestors
+const ANCESTOR_FACTOR: f64 = 0.8;
+
+/// Shortest keyword that may be used as a prefix of a longer term
+const MIN_PREFIX_LEN: usize = 2;
+
+/// How a term was matched by a keyword
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Match {
+    Exact,
+    /// The keyword is an ancestor of the term in the tag hierarchy, such as `lang` for `lang/rust`
+    Ancestor,
+    /// The keyword is a prefix of the term
+    Prefix,
+    /// The keyword differs from the term by the given number of edits
+    Typo(usize),
+}
+
+impl Match {
+    /// How much a match of this kind counts relative to an exact match
+    pub fn factor(&self) -> f64 {
+        match self {
+            Match::Exact => 1.0,
+            Match::Ancestor => ANCESTOR_FACTOR,
+            Match::Prefix => PREFIX_FACTOR,
+            Match::Typo(distance) => TYPO_FACTORS[distance - 1],
+        }
+    }
+}
+
+impl Display for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Match::Exact => f.write_str("exact"),
+            Match::Ancestor => f.write_str("ancestor"),
+            Match::Prefix => f.write_str("prefix"),
+            Match::Typo(1) => f.write_str("1 typo"),
+            Match::Typo(distance) => write!(f, "{} typos", distance),
+        }
+    }
+}
+
+/// Find the terms in the vocabulary that approximately match the keyword, either because the
+/// keyword is a prefix of the term or because they only differ by a small number of edits. The
+/// keyword itself is never included.
+pub fn expand<'a>(
+    keyword: &str,
+    vocabulary: impl IntoIterator<Item = &'a String>,
+) -> Vec<(String, Match)> {
+    let keyword_len: usize = keyword.chars().count();
+    let max_distance: usize = max_distance(keyword_len);
+
+    vocabulary
+        .into_iter()
+        .filter(|term| term.as_str() != keyword)
+        .filter_map(|term| {
+            if keyword_len >= MIN_PREFIX_LEN && term.starts_with(keyword) {
+                return Some((term.clone(), Match::Prefix));
+            }
+            match distance(keyword, term) {
+                d if d > 0 && d <= max_distance => Some((term.clone(), Match::Typo(d))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Number of typos that are tolerated for a keyword of the given length
+fn max_distance(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Levenshtein distance between two strings, counted in characters
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr: Vec<usize> = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution: usize = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vocabulary(terms: &[&str]) -> Vec<String> {
+        terms.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn distance_counts_edits() {
+        assert_eq!(distance("kubernetes", "kubernetes"), 0);
+        assert_eq!(distance("kubernete", "kubernetes"), 1);
+        assert_eq!(distance("rsut", "rust"), 2);
+        assert_eq!(distance("", "go"), 2);
+        assert_eq!(distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn expand_matches_prefix() {
+        let matches = expand("k8", &vocabulary(&["k8s", "rust", "k"]));
+        assert_eq!(matches, vec![(String::from("k8s"), Match::Prefix)]);
+    }
+
+    #[test]
+    fn expand_matches_typo() {
+        let matches = expand("kubrenetes", &vocabulary(&["kubernetes", "docs"]));
+        assert_eq!(matches, vec![(String::from("kubernetes"), Match::Typo(2))]);
+    }
+
+    #[test]
+    fn expand_does_not_tolerate_typos_in_short_keywords() {
+        let matches = expand("go", &vocabulary(&["so", "og", "do"]));
+        assert!(matches.is_empty());
+    }
+}
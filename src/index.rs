@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bookmark::{Bookmark, FileError};
+
+const INDEX_DIR: &str = ".index";
+const INDEX_FILE: &str = "bookmarks.json";
+
+/// Ignore file written to [INDEX_DIR] when it is created, which ignores everything in it so that the
+/// index is never committed when the data directory is synchronized with git
+const GITIGNORE: &str = ".gitignore";
+
+/// Directory inside [INDEX_DIR] with the indices of read-only sources, which are kept there since
+/// the directory of a source must not be modified
+const SOURCES_DIR: &str = "sources";
+
+/// Bump whenever the layout of [Entry] or [Bookmark] changes, so that indices written by an older
+/// version are rebuilt rather than trusted.
+const VERSION: u32 = 3;
+
+/// On-disk cache of all bookmarks in a data directory, so that each command does not have to parse
+/// every bookmark file. Entries are keyed by their path relative to the data directory and are
+/// refreshed whenever the modification time of the file differs from the one in the index.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    entries: BTreeMap<PathBuf, Entry>,
+    #[serde(skip)]
+    dir: PathBuf,
+    /// Path of the index file itself
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    modified: SystemTime,
+    bookmark: Bookmark,
+}
+
+impl Index {
+    /// Load the index for `dir` and bring it up to date with the bookmark files on disk. The index
+    /// is only written back if anything changed.
+    pub fn open(dir: &Path) -> Index {
+        Index::open_at(dir, &dir.join(INDEX_DIR).join(INDEX_FILE))
+    }
+
+    /// Load the index of the read-only source `name` in `source_dir`, which is kept in the index
+    /// directory of the data directory `dir`, and bring it up to date like [Index::open].
+    pub fn open_source(dir: &Path, name: &str, source_dir: &Path) -> Index {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let path: PathBuf = dir.join(INDEX_DIR).join(SOURCES_DIR).join(format!("{}.json", name));
+        Index::open_at(source_dir, &path)
+    }
+
+    fn open_at(dir: &Path, path: &Path) -> Index {
+        let mut index: Index = Index::load(dir, path);
+        if index.refresh() {
+            if let Err(e) = index.save() {
+                log::warn!("Unable to save index: {}", e);
+            }
+        }
+        index
+    }
+
+    /// Discard any existing index for `dir` and build a new one from scratch. The indices of
+    /// read-only sources are discarded as well, and rebuilt when the sources are next read.
+    pub fn rebuild(dir: &Path) -> Result<Index, FileError> {
+        let sources: PathBuf = dir.join(INDEX_DIR).join(SOURCES_DIR);
+        if sources.exists() {
+            std::fs::remove_dir_all(sources)?;
+        }
+        let mut index: Index = Index::empty(dir, &dir.join(INDEX_DIR).join(INDEX_FILE));
+        index.refresh();
+        index.save()?;
+        Ok(index)
+    }
+
+    fn empty(dir: &Path, path: &Path) -> Index {
+        Index {
+            version: VERSION,
+            entries: BTreeMap::new(),
+            dir: dir.to_path_buf(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn load(dir: &Path, path: &Path) -> Index {
+        let index: Option<Index> = match std::fs::read(path) {
+            Ok(bytes) => match serde_json::from_slice::<Index>(&bytes) {
+                Ok(index) if index.version == VERSION => Some(index),
+                Ok(_) => {
+                    log::info!("Index has an outdated format and will be rebuilt");
+                    None
+                }
+                Err(e) => {
+                    log::warn!("Unable to read index {:?}: {}", path, e);
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        match index {
+            Some(index) => Index {
+                dir: dir.to_path_buf(),
+                path: path.to_path_buf(),
+                ..index
+            },
+            None => Index::empty(dir, path),
+        }
+    }
+
+    /// Compare the index with the bookmark files on disk, parsing files that are new or have been
+    /// modified and dropping entries for files that no longer exist. Returns true if the index was
+    /// changed.
+    fn refresh(&mut self) -> bool {
+        let mut changed = false;
+        let mut entries: BTreeMap<PathBuf, Entry> = BTreeMap::new();
+
+        let files = walkdir::WalkDir::new(&self.dir)
+            .into_iter()
+            .filter_entry(|f| f.depth() == 0 || !is_hidden(f))
+            .filter_map(|f| f.ok())
+            .filter(|f| f.file_type().is_file());
+
+        for file in files {
+            let path: &Path = file.path();
+            let rel_path: PathBuf = match path.strip_prefix(&self.dir) {
+                Ok(rel_path) => rel_path.to_path_buf(),
+                Err(_) => continue,
+            };
+            let modified: Option<SystemTime> = file.metadata().ok().and_then(|m| m.modified().ok());
+
+            if let Some(entry) = self.entries.remove(&rel_path) {
+                if Some(entry.modified) == modified {
+                    entries.insert(rel_path, entry);
+                    continue;
+                }
+            }
+
+            changed = true;
+            match Bookmark::from_file(path) {
+                Ok(bookmark) => {
+                    let modified: SystemTime = modified.unwrap_or(SystemTime::UNIX_EPOCH);
+                    entries.insert(rel_path, Entry { modified, bookmark });
+                }
+                Err(e) => {
+                    log::error!("Unable to read {}: {}", path.to_str().unwrap_or_default(), e)
+                }
+            }
+        }
+
+        changed |= !self.entries.is_empty();
+        self.entries = entries;
+        changed
+    }
+
+    fn save(&self) -> Result<(), FileError> {
+        if let Some(index_dir) = self.path.parent() {
+            std::fs::create_dir_all(index_dir)?;
+        }
+        if let Some(index_dir) = self.path.ancestors().find(|p| p.ends_with(INDEX_DIR)) {
+            let gitignore: PathBuf = index_dir.join(GITIGNORE);
+            if !gitignore.exists() {
+                std::fs::write(gitignore, "*\n")?;
+            }
+        }
+        let json: Vec<u8> = serde_json::to_vec(self).map_err(|_| FileError::Serialize)?;
+        let tmp: PathBuf = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// All indexed bookmarks, together with the absolute path of their file
+    pub fn bookmarks(&self) -> impl Iterator<Item = (PathBuf, &Bookmark)> {
+        self.entries
+            .iter()
+            .map(move |(rel_path, entry)| (self.dir.join(rel_path), &entry.bookmark))
+    }
+}
+
+pub fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    entry.file_name().to_str().map(|f| f.starts_with('.')).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::temp_dir;
+    use itertools::Itertools;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    /// Write a bookmark to its file under `dir` with a modification time in the past, so that any
+    /// later write is certain to change the modification time
+    fn write(dir: &Path, url: &str, title: &str) -> PathBuf {
+        let bkm = Bookmark::new(url, Some(String::from(title)), HashSet::new()).unwrap();
+        let path: PathBuf = dir.join(bkm.rel_path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, bkm.to_yaml().unwrap()).unwrap();
+        let past: SystemTime = SystemTime::now() - Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+        path
+    }
+
+    fn titles(index: &Index) -> Vec<String> {
+        index.bookmarks().filter_map(|(_, bkm)| bkm.title()).sorted().collect()
+    }
+
+    #[test]
+    fn new_modified_and_deleted_files_are_refreshed() {
+        let dir: PathBuf = temp_dir("index-refresh");
+        let first: PathBuf = write(&dir, "https://example.com", "First");
+        assert_eq!(titles(&Index::open(&dir)), ["First"]);
+        let gitignore: PathBuf = dir.join(INDEX_DIR).join(GITIGNORE);
+        assert_eq!(std::fs::read_to_string(gitignore).unwrap(), "*\n");
+
+        write(&dir, "https://example.org", "Second");
+        assert_eq!(titles(&Index::open(&dir)), ["First", "Second"]);
+
+        let bkm =
+            Bookmark::new("https://example.com", Some(String::from("Changed")), HashSet::new());
+        std::fs::write(&first, bkm.unwrap().to_yaml().unwrap()).unwrap();
+        assert_eq!(titles(&Index::open(&dir)), ["Changed", "Second"]);
+
+        std::fs::remove_file(&first).unwrap();
+        assert_eq!(titles(&Index::open(&dir)), ["Second"]);
+    }
+
+    #[test]
+    fn hidden_directories_are_skipped_except_data_directory() {
+        let dir: PathBuf = temp_dir("index-hidden").join(".bookmarks");
+        write(&dir, "https://example.com", "Visible");
+        write(&dir.join(".git"), "https://example.org", "Hidden");
+        assert_eq!(titles(&Index::open(&dir)), ["Visible"]);
+    }
+
+    #[test]
+    fn index_of_outdated_version_is_rebuilt() {
+        let dir: PathBuf = temp_dir("index-version");
+        write(&dir, "https://example.com", "Fresh");
+        let index: Index = Index::open(&dir);
+
+        // Entries of an index with another version are not trusted, even if the files are unchanged
+        let stale: Index = Index {
+            version: VERSION - 1,
+            entries: index
+                .entries
+                .into_iter()
+                .map(|(path, entry)| {
+                    let bookmark = entry.bookmark.with_title(Some(String::from("Stale")));
+                    (path, Entry { bookmark, ..entry })
+                })
+                .collect(),
+            ..index
+        };
+        stale.save().unwrap();
+        assert_eq!(titles(&Index::open(&dir)), ["Fresh"]);
+
+        let current = Index {
+            version: VERSION,
+            ..stale
+        };
+        current.save().unwrap();
+        assert_eq!(titles(&Index::open(&dir)), ["Stale"]);
+    }
+}
@@ -1,10 +1,27 @@
+use std::cell::RefCell;
 use std::io::{BufWriter, Stderr, Stdout, Write};
-use std::{collections::HashSet, convert::TryInto};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+};
 
+use crate::fuzzy;
+use crate::suggest::Suggestion;
 use crate::tag::{Tag, TagHolder};
-use dialoguer::{console::Term, theme::Theme, Input};
+use dialoguer::{
+    console::{style, Term},
+    theme::Theme,
+    Completion, Confirm, Editor, Input,
+};
+use itertools::Itertools;
 use url::Url;
 
+/// Largest number of edits between a new tag and a known tag for them to be considered similar
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// Most known tags that are listed with their counts above the tag prompt
+const MAX_LISTED_TAGS: usize = 10;
+
 pub struct Streams {
     ui: BufWriter<Stderr>,
     output: BufWriter<Stdout>,
@@ -56,16 +73,208 @@ pub fn read_title(default: Option<String>, theme: &dyn Theme, term: &Term) -> Op
         .ok()
 }
 
-pub fn read_tags(default: impl TagHolder, theme: &dyn Theme, term: &Term) -> HashSet<Tag> {
-    let tags: std::io::Result<String> = Input::with_theme(theme)
-        .with_prompt("Tags")
+pub fn read_description(default: Option<String>, theme: &dyn Theme, term: &Term) -> Option<String> {
+    let description: String = Input::with_theme(theme)
+        .with_prompt("Description")
         .allow_empty(true)
-        .with_initial_text(default.join())
-        .interact_text_on(term);
+        .with_initial_text(default.unwrap_or_default())
+        .interact_text_on(term)
+        .ok()?;
+
+    match description.trim() {
+        "" => None,
+        description => Some(description.to_string()),
+    }
+}
+
+/// Edit notes in the editor of the user, as given by `$VISUAL` or `$EDITOR`. Returns `None` if
+/// the notes were not saved in the editor, and `Some(None)` if they were saved empty.
+pub fn edit_notes(notes: Option<String>) -> std::io::Result<Option<Option<String>>> {
+    let notes: Option<String> = Editor::new().extension(".md").edit(&notes.unwrap_or_default())?;
+    let notes = notes.map(|notes| match notes.trim() {
+        "" => None,
+        _ => Some(notes),
+    });
+
+    Ok(notes)
+}
+
+/// Read tags from the user, where the tag being typed can be completed with tab from the known
+/// tags, the most used first. Pressing tab before typing a tag picks one of the tags suggested by
+/// `suggest` for the tags entered so far instead. The most used tags with their counts, and the
+/// suggested tags, are listed above the prompt. Tags that are not known are highlighted once
+/// entered, together with any similar known tags, and the user is asked whether to keep them.
+pub fn read_tags(
+    default: impl TagHolder,
+    known: &HashMap<Tag, usize>,
+    suggest: &dyn Fn(&HashSet<Tag>) -> Vec<Suggestion>,
+    theme: &dyn Theme,
+    term: &Term,
+) -> HashSet<Tag> {
+    let completion = TagCompletion::new(known, suggest);
+    let mut initial: String = default.join();
+    let suggestions: Vec<Suggestion> = suggest(&Tag::new_set(initial.as_str()));
+    if write_hints(&completion, &suggestions, term).is_err() {
+        log::warn!("Unable to list known and suggested tags");
+    }
+    loop {
+        let tags: std::io::Result<String> = Input::with_theme(theme)
+            .with_prompt("Tags (tab to complete)")
+            .allow_empty(true)
+            .with_initial_text(initial)
+            .completion_with(&completion)
+            .interact_text_on(term);
+
+        let input: String = match tags {
+            Ok(input) => input,
+            Err(_) => return default.tags().into_iter().map(Tag::canonical).collect(),
+        };
+        let tags: HashSet<Tag> = Tag::new_set(input.as_str());
+        if !term.is_term() || confirm_new_tags(&tags, known, theme, term) {
+            return tags;
+        }
+        initial = input;
+    }
+}
+
+/// List the most used known tags with their counts, and the suggested tags, if there are any
+fn write_hints(
+    completion: &TagCompletion,
+    suggestions: &[Suggestion],
+    term: &Term,
+) -> std::io::Result<()> {
+    let most_used: String = completion
+        .tags
+        .iter()
+        .take(MAX_LISTED_TAGS)
+        .map(|(tag, count)| format!("{} ({})", tag, count))
+        .join(", ");
+    if !most_used.is_empty() {
+        term.write_line(&format!("Most used tags: {}", most_used))?;
+    }
+    let suggested: String = suggestions.iter().join(", ");
+    if !suggested.is_empty() {
+        term.write_line(&format!("Suggested tags (tab before typing a tag): {}", suggested))?;
+    }
+    Ok(())
+}
+
+/// Highlight the tags that are not known and ask the user whether to keep them, which is always
+/// the case if all tags are known
+fn confirm_new_tags(
+    tags: &HashSet<Tag>,
+    known: &HashMap<Tag, usize>,
+    theme: &dyn Theme,
+    term: &Term,
+) -> bool {
+    let new_tags: Vec<&Tag> = tags.iter().filter(|t| !known.contains_key(t)).sorted().collect();
+    if new_tags.is_empty() || known.is_empty() {
+        return true;
+    }
+
+    for tag in new_tags {
+        let similar: String = known
+            .iter()
+            .filter(|(k, _)| fuzzy::distance(k.as_ref(), tag.as_ref()) <= MAX_TYPO_DISTANCE)
+            .sorted_by(|(t0, c0), (t1, c1)| c1.cmp(c0).then(t0.cmp(t1)))
+            .map(|(k, count)| format!("{} ({})", k, count))
+            .join(", ");
+        let line: String = match similar.as_str() {
+            "" => format!("New tag {}", style(tag).yellow().bold()),
+            _ => format!("New tag {}, similar to {}", style(tag).yellow().bold(), similar),
+        };
+        if term.write_line(&line).is_err() {
+            return true;
+        }
+    }
+
+    Confirm::with_theme(theme)
+        .with_prompt("Keep new tags?")
+        .default(true)
+        .interact_on(term)
+        .unwrap_or(true)
+}
 
-    match tags {
-        Ok(tags) => Tag::new_set(tags),
-        Err(_) => default.tags(),
+/// Completion of the last tag of the input from the known tags, or from the suggested tags if
+/// nothing of the last tag has been typed yet. Completing again right after a completion cycles
+/// through the other candidates.
+struct TagCompletion<'a> {
+    /// Known tags and how many bookmarks have them, the most used first
+    tags: Vec<(Tag, usize)>,
+    suggest: &'a dyn Fn(&HashSet<Tag>) -> Vec<Suggestion>,
+    cycle: RefCell<Option<Cycle>>,
+}
+
+struct Cycle {
+    /// Input before the tag that is being completed
+    head: String,
+    /// Text of the tag that was typed before the first completion
+    prefix: String,
+    index: usize,
+    /// Input after the latest completion
+    completed: String,
+}
+
+impl<'a> TagCompletion<'a> {
+    fn new(
+        known: &HashMap<Tag, usize>,
+        suggest: &'a dyn Fn(&HashSet<Tag>) -> Vec<Suggestion>,
+    ) -> TagCompletion<'a> {
+        let tags: Vec<(Tag, usize)> = known
+            .iter()
+            .sorted_by(|(t0, c0), (t1, c1)| c1.cmp(c0).then(t0.cmp(t1)))
+            .map(|(tag, count)| (tag.clone(), *count))
+            .collect();
+
+        TagCompletion {
+            tags,
+            suggest,
+            cycle: RefCell::new(None),
+        }
+    }
+
+    /// Candidates for the tag `prefix` that follows the tags in `head`
+    fn candidates(&self, head: &str, prefix: &str) -> Vec<Tag> {
+        if prefix.is_empty() {
+            let suggestions: Vec<Suggestion> = (self.suggest)(&Tag::new_set(head));
+            return suggestions.into_iter().map(|s| s.tag).collect();
+        }
+        self.tags
+            .iter()
+            .map(|(tag, _)| tag)
+            .filter(|tag| tag.as_ref().starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Completion for TagCompletion<'_> {
+    fn get(&self, input: &str) -> Option<String> {
+        let mut cycle = self.cycle.borrow_mut();
+        let (head, prefix, index): (String, String, usize) = match cycle.take() {
+            Some(c) if c.completed == input => (c.head, c.prefix, c.index + 1),
+            _ => {
+                let split: usize = input.rfind([' ', ',']).map(|i| i + 1).unwrap_or(0);
+                let (head, prefix) = input.split_at(split);
+                (head.to_string(), prefix.to_lowercase(), 0)
+            }
+        };
+
+        let candidates: Vec<Tag> = self.candidates(&head, &prefix);
+        if candidates.is_empty() {
+            return None;
+        }
+        let index: usize = index % candidates.len();
+        let tag: &Tag = &candidates[index];
+        let completed: String = format!("{}{}", head, tag);
+        *cycle = Some(Cycle {
+            head,
+            prefix,
+            index,
+            completed: completed.clone(),
+        });
+
+        Some(completed)
     }
 }
 
@@ -85,3 +294,53 @@ pub fn read_url(default: Url, theme: &dyn Theme, term: &Term) -> Url {
         None => default,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::{make_bookmark, Bookmark};
+    use crate::suggest;
+
+    fn no_suggestions(_: &HashSet<Tag>) -> Vec<Suggestion> {
+        Vec::new()
+    }
+
+    fn completion() -> TagCompletion<'static> {
+        let known: HashMap<Tag, usize> = [("rust", 1), ("ruby", 3), ("go", 2)]
+            .into_iter()
+            .map(|(tag, count)| (Tag::new(tag).unwrap(), count))
+            .collect();
+        TagCompletion::new(&known, &no_suggestions)
+    }
+
+    #[test]
+    fn completes_last_tag_with_most_used_tag() {
+        let completion: TagCompletion = completion();
+        assert_eq!(completion.get("go r"), Some(String::from("go ruby")));
+        assert_eq!(completion.get("go x"), None);
+        assert_eq!(completion.get("go "), None);
+    }
+
+    #[test]
+    fn repeated_completion_cycles_through_candidates() {
+        let completion: TagCompletion = completion();
+        assert_eq!(completion.get("r"), Some(String::from("ruby")));
+        assert_eq!(completion.get("ruby"), Some(String::from("rust")));
+        assert_eq!(completion.get("rust"), Some(String::from("ruby")));
+    }
+
+    #[test]
+    fn completion_before_typing_a_tag_cycles_through_suggestions() {
+        let bookmarks: Vec<Bookmark> = vec![
+            make_bookmark("https://docs.rs/serde", None, &["rust", "docs"]),
+            make_bookmark("https://docs.rs/tokio", None, &["rust", "docs", "async"]),
+        ];
+        let url = Url::parse("https://docs.rs/clap").unwrap();
+        let suggest = |tags: &HashSet<Tag>| suggest::suggest(&bookmarks, &url, tags);
+        let completion = TagCompletion::new(&HashMap::new(), &suggest);
+        assert_eq!(completion.get(""), Some(String::from("docs")));
+        assert_eq!(completion.get("docs"), Some(String::from("rust")));
+        assert_eq!(completion.get("rust "), Some(String::from("rust docs")));
+        assert_eq!(completion.get("rust docs"), Some(String::from("rust async")));
+    }
+}
@@ -1,53 +1,191 @@
 mod bookmark;
 mod cfg;
 mod cmd;
+mod fuzzy;
+mod index;
 mod io;
 mod logger;
 #[cfg(feature = "migrate")]
 mod migrate;
+mod query;
+mod rank;
+mod rules;
+mod search;
+mod storage;
+mod suggest;
 mod tag;
+mod time;
+mod visits;
 
-use crate::cfg::Config;
+use crate::cfg::{Config, Settings};
 use crate::logger::setup_logging;
 use bookmark::FileError;
 use clap::Parser;
 use dialoguer::theme::Theme;
+use rules::Rules;
 use std::io::Write;
 use std::{path::PathBuf, process};
+use storage::Storage;
+use time::Period;
+use visits::Visits;
 
 fn main() -> Result<(), Error> {
+    let mut settings: Settings = Settings::load()?;
     let cfg: Config = Config::parse();
     setup_logging(cfg.verbosity_level);
+    settings.apply(&cfg)?;
+    tag::set_aliases(&settings.aliases);
+    tag::set_fold_diacritics(settings.fold_diacritics);
+    let rules = Rules::new(&settings.rules)?;
 
     let mut streams = io::Streams::new();
 
-    let dir: PathBuf = dir().expect("Unable to find data directory");
+    let dir: PathBuf = settings.dir().expect("Unable to find data directory");
+
+    if let Some(profile) = &settings.ignored_profile {
+        let msg = format!("Ignoring profile {} since a data directory is given", profile);
+        writeln!(streams.ui(), "{}", msg)?;
+    }
 
     if cfg.print_dbg {
+        if let Some(path) = &settings.path {
+            writeln!(streams.ui(), "Using configuration file {:?}", path)?;
+        }
+        match (&settings.profile, &settings.dir) {
+            (Some(profile), _) => writeln!(streams.ui(), "Using profile {}", profile)?,
+            (None, Some(_)) => writeln!(streams.ui(), "Using no profile")?,
+            (None, None) => writeln!(streams.ui(), "Using default profile")?,
+        }
         writeln!(streams.ui(), "Using data directory {:?}", &dir)?;
         drop(streams);
         process::exit(0);
     }
 
+    log::debug!("Using configuration file {:?}", &settings.path);
+    log::debug!("Using profile {:?}", &settings.profile);
     log::debug!("Using data directory {:?}", &dir);
-    let theme: Box<dyn Theme> = cfg.theme();
+    let theme: Box<dyn Theme> = settings.theme();
+    let storage: Box<dyn Storage> = storage::open(settings.storage, &dir)?;
+    let storage: Box<dyn Storage> = storage::with_sources(storage, &dir, settings.sources());
+    let mut visits = Visits::load(&dir);
 
-    match cfg.cmd.unwrap_or_default() {
-        cmd::Command::Add { url, tags } => cmd::add(streams, &dir, url, tags, &*theme),
+    match cfg.cmd.unwrap_or_else(|| settings.command.into()) {
+        cmd::Command::Add {
+            url,
+            tags,
+            title,
+            stdin: _,
+            from_file,
+            no_fetch,
+            yes,
+        } => {
+            let fetch_title: bool = settings.fetch_title && !no_fetch;
+            match url {
+                Some(url) => cmd::add(
+                    streams,
+                    &*storage,
+                    url,
+                    tags,
+                    title,
+                    &rules,
+                    fetch_title,
+                    yes,
+                    &*theme,
+                ),
+                None => cmd::add_from(streams, &*storage, from_file, &rules, fetch_title),
+            }
+        }
+        cmd::Command::Set {
+            url,
+            title,
+            add_tags,
+            remove_tags,
+        } => cmd::set(streams, &*storage, url, title, add_tags, remove_tags),
         cmd::Command::Open {
             min_score,
             keywords,
-        } => cmd::open(streams, &dir, keywords, min_score),
+        } => {
+            let min_score: f64 = min_score.unwrap_or(settings.min_score);
+            cmd::open(streams, &*storage, &mut visits, &settings.search, keywords, min_score)
+        }
         cmd::Command::Select {
             min_score,
             limit,
+            since,
+            before,
             keywords,
-        } => cmd::select(streams, &dir, keywords, limit, min_score, &*theme),
+        } => cmd::select(
+            streams,
+            &*storage,
+            &mut visits,
+            keywords,
+            Period::new(since, before),
+            limit.unwrap_or(settings.limit),
+            min_score.unwrap_or(settings.min_score),
+            settings.fetch_title,
+            &*theme,
+        ),
         cmd::Command::List {
-            tags,
+            keywords,
             format,
             delimiter,
-        } => cmd::list(streams, &dir, tags, format, delimiter),
+            since,
+            before,
+        } => {
+            let format: Vec<cmd::FormatField> = format.unwrap_or(settings.list.format);
+            let delimiter: String = delimiter.unwrap_or(settings.list.delimiter);
+            let period = Period::new(since, before);
+            cmd::list(streams, &*storage, keywords, period, format, delimiter)
+        }
+        cmd::Command::Explain {
+            min_score,
+            limit,
+            keywords,
+        } => {
+            let min_score: f64 = min_score.unwrap_or(settings.min_score);
+            cmd::explain(streams, &*storage, &visits, keywords, limit, min_score)
+        }
+        cmd::Command::Edit {
+            min_score,
+            keywords,
+        } => {
+            let min_score: f64 = min_score.unwrap_or(settings.min_score);
+            cmd::edit(streams, &*storage, &visits, keywords, min_score, &*theme)
+        }
+        cmd::Command::Bulk {
+            query,
+            add_tags,
+            remove_tags,
+            set_title_from_page,
+            dry_run,
+            yes,
+        } => cmd::bulk(
+            streams,
+            &*storage,
+            query,
+            add_tags,
+            remove_tags,
+            set_title_from_page,
+            dry_run,
+            yes,
+            &*theme,
+        ),
+        cmd::Command::Tag { cmd } => cmd::tag(streams, &*storage, cmd),
+        cmd::Command::Retag { dry_run } => cmd::retag(streams, &*storage, &rules, dry_run),
+        cmd::Command::Config => {
+            let settings = Settings {
+                dir: Some(dir),
+                ..settings
+            };
+            cmd::config(streams, &settings)
+        }
+        cmd::Command::Reindex => cmd::reindex(streams, &dir, settings.storage),
+        cmd::Command::Fmt { check } => {
+            if !cmd::fmt(streams, &dir, settings.storage, check)? {
+                process::exit(1);
+            }
+            Ok(())
+        }
         #[cfg(feature = "migrate")]
         cmd::Command::Migrate => migrate::migrate(streams, &dir),
     }
@@ -59,30 +197,40 @@ pub enum Error {
     Formatting,
     Serialization,
     OpenUrl,
+    InvalidQuery(query::ParseError),
+    InvalidUrl(String),
+    Config(String),
     Other,
 }
 
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
-        log::error!("{}", e.to_string());
+        log::error!("{}", e);
         Self::NotExistingFile
     }
 }
 
 impl From<std::fmt::Error> for Error {
     fn from(e: std::fmt::Error) -> Self {
-        log::error!("{}", e.to_string());
+        log::error!("{}", e);
         Self::Formatting
     }
 }
 
 impl From<serde_yaml::Error> for Error {
     fn from(e: serde_yaml::Error) -> Self {
-        log::error!("{}", e.to_string());
+        log::error!("{}", e);
         Self::Serialization
     }
 }
 
+impl From<query::ParseError> for Error {
+    fn from(e: query::ParseError) -> Self {
+        log::error!("{}", e);
+        Self::InvalidQuery(e)
+    }
+}
+
 impl From<FileError> for Error {
     fn from(e: FileError) -> Self {
         match e {
@@ -92,19 +240,3 @@ impl From<FileError> for Error {
         }
     }
 }
-
-fn dir() -> Option<PathBuf> {
-    match dirs_next::data_dir() {
-        Some(mut dir) => {
-            dir.push("goto");
-            Some(dir)
-        }
-        None => match dirs_next::home_dir() {
-            Some(mut dir) => {
-                dir.push(".goto");
-                Some(dir)
-            }
-            None => None,
-        },
-    }
-}
@@ -1,10 +1,11 @@
-use crate::{bookmark::FileError, io::Streams, Error};
+use crate::{bookmark::FileError, index::is_hidden, io::Streams, Error};
 use std::io::Write;
 use std::path::Path;
 
 pub fn migrate(mut streams: Streams, dir: &std::path::PathBuf) -> Result<(), Error> {
     let sum: usize = walkdir::WalkDir::new(dir)
         .into_iter()
+        .filter_entry(|f| f.depth() == 0 || !is_hidden(f))
         .filter_map(|f| f.ok())
         .filter(|f| f.file_name().to_str().unwrap_or_default().ends_with(".json"))
         .inspect(|f| writeln!(streams.ui(), "Migrating {:?}", f.path()).unwrap())
@@ -75,7 +76,7 @@ impl std::fmt::Display for Extension {
 
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
-        log::error!("{}", e.to_string());
+        log::error!("{}", e);
         Self::Serialization
     }
 }
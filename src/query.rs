@@ -0,0 +1,421 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::bookmark::Bookmark;
+use crate::tag::Tag;
+
+lazy_static! {
+    static ref FIELD: Regex = Regex::new(r"^([a-zA-Z]+):(.*)$").unwrap();
+}
+
+/// A parsed query, which matches a bookmark if all of its clauses match.
+///
+/// The syntax of a query is a list of clauses, where each clause is
+/// - a keyword, such as `rust`
+/// - a field filter, such as `domain:github.com`, `title:"release notes"`, `url:/wiki/` or
+///   `scheme:http`
+/// - alternatives of either keywords or field filters separated by `|`, such as `rust|go`
+/// - any of the above prefixed with `-` to exclude bookmarks matching it, such as `-archived`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    clauses: Vec<Clause>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    negated: bool,
+    alternatives: Alternatives,
+}
+
+/// Alternatives of a clause, of which at least one must match. Keywords and field filters can not
+/// be mixed as alternatives, since keywords are ranked while field filters are not.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alternatives {
+    Keywords(Vec<Tag>),
+    Filters(Vec<Filter>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Domain of the URL, or any parent domain of it
+    Domain(String),
+    /// Case insensitive substring of the title
+    Title(String),
+    /// Substring of the full URL
+    Url(String),
+    Scheme(String),
+}
+
+impl Query {
+    /// Parse a query from command line arguments. A single argument may contain several clauses
+    /// separated by whitespace, unless it is a field filter, in which case the rest of the argument
+    /// is its value, so `title:"release notes"` and `'title:release notes'` are equivalent.
+    pub fn parse<T: AsRef<str>>(args: &[T]) -> Result<Query, ParseError> {
+        let mut clauses: Vec<Clause> = Vec::with_capacity(args.len());
+        for arg in args {
+            let arg: &str = arg.as_ref().trim();
+            if is_single_filter(arg) {
+                clauses.push(Clause::parse(arg)?);
+            } else {
+                for token in tokenize(arg)? {
+                    clauses.push(Clause::parse(&token)?);
+                }
+            }
+        }
+
+        Ok(Query { clauses })
+    }
+
+    /// Check if the bookmark matches all clauses of the query, where keywords must be present
+    /// among the tags of the bookmark
+    pub fn matches(&self, bkm: &Bookmark) -> bool {
+        self.clauses.iter().all(|c| c.matches(bkm))
+    }
+
+    /// Check if the bookmark matches the parts of the query that are not ranked, that is all
+    /// negated clauses and all field filters
+    pub fn matches_filters(&self, bkm: &Bookmark) -> bool {
+        self.clauses
+            .iter()
+            .filter(|c| c.negated || matches!(c.alternatives, Alternatives::Filters(_)))
+            .all(|c| c.matches(bkm))
+    }
+
+    /// Keywords that should be used for ranking, where each entry contains the alternatives of one
+    /// clause
+    pub fn keywords(&self) -> Vec<Vec<Tag>> {
+        self.clauses
+            .iter()
+            .filter(|c| !c.negated)
+            .filter_map(|c| match &c.alternatives {
+                Alternatives::Keywords(tags) => Some(tags.clone()),
+                Alternatives::Filters(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl Clause {
+    fn parse(token: &str) -> Result<Clause, ParseError> {
+        let (negated, token): (bool, &str) = match token.strip_prefix('-') {
+            Some(token) => (true, token),
+            None => (false, token),
+        };
+
+        if token.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        let atoms: Vec<Atom> = split_alternatives(token)?
+            .iter()
+            .map(|atom| Atom::parse(atom))
+            .collect::<Result<_, _>>()?;
+
+        let alternatives: Alternatives = if atoms.iter().all(|a| matches!(a, Atom::Keyword(_))) {
+            // Synonyms of a keyword are alternatives to it, so that bookmarks that are tagged with
+            // an alias rather than with the canonical tag still match
+            let tags = atoms
+                .into_iter()
+                .filter_map(|a| a.into_keyword())
+                .flat_map(|tag| tag.synonyms())
+                .unique()
+                .collect();
+            Alternatives::Keywords(tags)
+        } else if atoms.iter().all(|a| matches!(a, Atom::Filter(_))) {
+            let filters = atoms.into_iter().filter_map(|a| a.into_filter()).collect();
+            Alternatives::Filters(filters)
+        } else {
+            return Err(ParseError::MixedAlternatives(token.to_string()));
+        };
+
+        Ok(Clause {
+            negated,
+            alternatives,
+        })
+    }
+
+    fn matches(&self, bkm: &Bookmark) -> bool {
+        let matches: bool = match &self.alternatives {
+            Alternatives::Keywords(tags) => {
+                let tags: Vec<Tag> = tags.iter().map(Tag::folded).collect();
+                tags.iter().any(|t| bkm.tags().iter().any(|tag| tag.folded().is_within(t)))
+            }
+            Alternatives::Filters(filters) => filters.iter().any(|f| f.matches(bkm)),
+        };
+        matches != self.negated
+    }
+}
+
+enum Atom {
+    Keyword(Tag),
+    Filter(Filter),
+}
+
+impl Atom {
+    fn parse(atom: &str) -> Result<Atom, ParseError> {
+        match split_field(atom) {
+            Some((field, value)) => {
+                Filter::new(field, unquote(value).to_string()).map(Atom::Filter)
+            }
+            None => match Tag::new(unquote(atom)) {
+                Ok(tag) => Ok(Atom::Keyword(tag)),
+                Err(_) => Err(ParseError::InvalidKeyword(atom.to_string())),
+            },
+        }
+    }
+
+    fn into_keyword(self) -> Option<Tag> {
+        match self {
+            Atom::Keyword(tag) => Some(tag),
+            Atom::Filter(_) => None,
+        }
+    }
+
+    fn into_filter(self) -> Option<Filter> {
+        match self {
+            Atom::Keyword(_) => None,
+            Atom::Filter(filter) => Some(filter),
+        }
+    }
+}
+
+impl Filter {
+    fn new(field: &str, value: String) -> Result<Filter, ParseError> {
+        let field: String = field.to_lowercase();
+        if value.trim().is_empty() {
+            return Err(ParseError::EmptyValue(field));
+        }
+        match field.as_str() {
+            "domain" => Ok(Filter::Domain(value.to_lowercase())),
+            "title" => Ok(Filter::Title(value.to_lowercase())),
+            "url" => Ok(Filter::Url(value)),
+            "scheme" => Ok(Filter::Scheme(value.to_lowercase())),
+            _ => Err(ParseError::UnknownField(field)),
+        }
+    }
+
+    fn matches(&self, bkm: &Bookmark) -> bool {
+        match self {
+            Filter::Domain(domain) => match bkm.domain() {
+                Some(d) => d == domain || d.ends_with(&format!(".{}", domain)),
+                None => false,
+            },
+            Filter::Title(title) => {
+                bkm.title().map(|t| t.to_lowercase().contains(title)).unwrap_or(false)
+            }
+            Filter::Url(url) => bkm.url().as_str().contains(url.as_str()),
+            Filter::Scheme(scheme) => bkm.url().scheme() == scheme,
+        }
+    }
+}
+
+/// Check if the argument is a single field filter with an unquoted value, in which case any
+/// whitespace is part of the value rather than separating clauses
+fn is_single_filter(arg: &str) -> bool {
+    let arg: &str = arg.strip_prefix('-').unwrap_or(arg);
+    match split_field(arg) {
+        Some((_, value)) => !value.contains(['"', '|']),
+        None => false,
+    }
+}
+
+/// Split a field filter into its field and value. Anything that looks like a URL, such as
+/// `https://example.com`, or a path, such as `std::vec`, is not considered a field filter.
+fn split_field(atom: &str) -> Option<(&str, &str)> {
+    let captures = FIELD.captures(atom)?;
+    let (field, value) = (captures.get(1)?.as_str(), captures.get(2)?.as_str());
+    if value.starts_with("//") || value.starts_with(':') {
+        None
+    } else {
+        Some((field, value))
+    }
+}
+
+/// Split on whitespace that is not inside double quotes
+fn tokenize(input: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ParseError::UnterminatedQuote(input.to_string()));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Split on `|` that is not inside double quotes
+fn split_alternatives(token: &str) -> Result<Vec<String>, ParseError> {
+    let mut alternatives: Vec<String> = Vec::new();
+    let mut alternative = String::new();
+    let mut quoted = false;
+    for c in token.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                alternative.push(c);
+            }
+            '|' if !quoted => alternatives.push(std::mem::take(&mut alternative)),
+            c => alternative.push(c),
+        }
+    }
+    alternatives.push(alternative);
+
+    if alternatives.iter().any(|a| a.is_empty()) {
+        Err(ParseError::EmptyAlternative(token.to_string()))
+    } else {
+        Ok(alternatives)
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    EmptyAlternative(String),
+    EmptyValue(String),
+    InvalidKeyword(String),
+    MixedAlternatives(String),
+    UnknownField(String),
+    UnterminatedQuote(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => f.write_str("Empty keyword after '-'"),
+            ParseError::EmptyAlternative(token) => {
+                write!(
+                    f,
+                    "Empty alternative in '{}', expected a keyword or filter around '|'",
+                    token
+                )
+            }
+            ParseError::EmptyValue(field) => write!(f, "Missing value for field '{}'", field),
+            ParseError::InvalidKeyword(keyword) => {
+                write!(f, "Keyword '{}' contains no valid characters", keyword)
+            }
+            ParseError::MixedAlternatives(token) => write!(
+                f,
+                "Keywords and field filters can not be alternatives to each other in '{}'",
+                token
+            ),
+            ParseError::UnknownField(field) => {
+                write!(f, "Unknown field '{}', expected one of domain, title, url or scheme", field)
+            }
+            ParseError::UnterminatedQuote(input) => write!(f, "Unterminated quote in '{}'", input),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::make_bookmark;
+
+    fn tags(tags: &[&str]) -> Vec<Tag> {
+        tags.iter().filter_map(|t| Tag::new(*t).ok()).collect()
+    }
+
+    #[test]
+    fn parse_keywords_negation_and_alternatives() {
+        let query: Query = Query::parse(&["rust", "-archived docs|wiki"]).unwrap();
+        assert_eq!(query.clauses.len(), 3);
+        assert!(query.clauses[1].negated);
+        assert_eq!(query.keywords(), vec![tags(&["rust"]), tags(&["docs", "wiki"])]);
+    }
+
+    #[test]
+    fn parse_field_filters() {
+        let query: Query = Query::parse(&[
+            "domain:github.com",
+            r#"title:"release notes""#,
+            "url:/wiki/",
+        ])
+        .unwrap();
+        let filters: Vec<Alternatives> =
+            query.clauses.into_iter().map(|c| c.alternatives).collect();
+        assert_eq!(
+            filters,
+            vec![
+                Alternatives::Filters(vec![Filter::Domain(String::from("github.com"))]),
+                Alternatives::Filters(vec![Filter::Title(String::from("release notes"))]),
+                Alternatives::Filters(vec![Filter::Url(String::from("/wiki/"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unquoted_field_value_in_single_argument_keeps_whitespace() {
+        let query: Query = Query::parse(&["title:release notes"]).unwrap();
+        assert_eq!(query, Query::parse(&[r#"title:"release notes""#]).unwrap());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Query::parse(&["foo:bar"]), Err(ParseError::UnknownField(String::from("foo"))));
+        assert_eq!(Query::parse(&["-"]), Err(ParseError::Empty));
+        assert_eq!(Query::parse(&["a|"]), Err(ParseError::EmptyAlternative(String::from("a|"))));
+        assert!(matches!(
+            Query::parse(&["rust|domain:github.com"]),
+            Err(ParseError::MixedAlternatives(_))
+        ));
+        assert!(matches!(
+            Query::parse(&[r#""release notes"#]),
+            Err(ParseError::UnterminatedQuote(_))
+        ));
+    }
+
+    #[test]
+    fn field_without_value_is_an_error() {
+        assert_eq!(Query::parse(&["title:"]), Err(ParseError::EmptyValue(String::from("title"))));
+    }
+
+    #[test]
+    fn urls_are_keywords_rather_than_fields() {
+        let query: Query = Query::parse(&["https://example.com", "std::vec"]).unwrap();
+        assert_eq!(query.keywords().len(), 2);
+    }
+
+    #[test]
+    fn matches_evaluates_all_clauses() {
+        let bkm: Bookmark =
+            make_bookmark("https://docs.github.com/en", Some("Release Notes"), &["git", "docs"]);
+        assert!(Query::parse(&["git", "domain:github.com"]).unwrap().matches(&bkm));
+        assert!(Query::parse(&["rust|docs", "scheme:https"]).unwrap().matches(&bkm));
+        assert!(Query::parse(&["title:release"]).unwrap().matches(&bkm));
+        assert!(!Query::parse(&["git", "-docs"]).unwrap().matches(&bkm));
+        assert!(!Query::parse(&["domain:hub.com"]).unwrap().matches(&bkm));
+    }
+
+    #[test]
+    fn matches_filters_ignores_ranked_keywords() {
+        let bkm: Bookmark = make_bookmark("https://github.com", None, &["git"]);
+        assert!(Query::parse(&["rust", "url:github"]).unwrap().matches_filters(&bkm));
+        assert!(!Query::parse(&["rust", "-git"]).unwrap().matches_filters(&bkm));
+    }
+}
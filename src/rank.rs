@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::bookmark::Bookmark;
+use crate::fuzzy::{self, Match};
+use crate::tag::{self, Tag};
+
+lazy_static! {
+    static ref WORD_SEPARATOR: Regex = Regex::new(r"[^\p{Alphabetic}\p{Number}]+").unwrap();
+}
+
+/// Term frequency saturation, a higher value lets repeated occurrences of a term in a bookmark
+/// contribute more to its score
+const K1: f64 = 1.2;
+
+/// Length normalization, where 0.0 means that the length of a field does not matter at all and
+/// 1.0 means that matches in long fields are fully scaled down by their length
+const B: f64 = 0.75;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Field {
+    Tags,
+    Title,
+    Url,
+    Notes,
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Field::Tags => f.write_str("tags"),
+            Field::Title => f.write_str("title"),
+            Field::Url => f.write_str("url"),
+            Field::Notes => f.write_str("notes"),
+        }
+    }
+}
+
+impl Field {
+    const ALL: [Field; 4] = [Field::Tags, Field::Title, Field::Url, Field::Notes];
+
+    fn weight(&self) -> f64 {
+        match self {
+            Field::Tags => 3.0,
+            Field::Title => 2.0,
+            Field::Url => 1.0,
+            Field::Notes => 0.5,
+        }
+    }
+}
+
+/// The terms of a single field of a bookmark, with the number of occurrences of each term
+#[derive(Debug, Default)]
+struct FieldTerms {
+    counts: HashMap<String, usize>,
+    len: usize,
+}
+
+impl FieldTerms {
+    fn new(terms: Vec<String>) -> FieldTerms {
+        let len: usize = terms.len();
+        let mut counts: HashMap<String, usize> = HashMap::with_capacity(len);
+        for term in terms {
+            *counts.entry(term).or_default() += 1;
+        }
+        FieldTerms { counts, len }
+    }
+}
+
+struct Document {
+    fields: [FieldTerms; 4],
+}
+
+impl Document {
+    fn new(bkm: &Bookmark) -> Document {
+        Document {
+            fields: Field::ALL.map(|field| FieldTerms::new(terms(bkm, field))),
+        }
+    }
+
+    fn field(&self, field: Field) -> &FieldTerms {
+        &self.fields[field as usize]
+    }
+
+    fn contains(&self, term: &str) -> bool {
+        self.fields.iter().any(|f| f.counts.contains_key(term))
+    }
+}
+
+/// Rank bookmarks by their relevance for the given keywords, where each keyword consists of one or
+/// more alternative tags of which the best matching one is used. Ranking is done with a BM25F
+/// model over the tags, title, URL and notes of each bookmark. Matches in tags weigh more than
+/// matches in the title, which in turn weigh more than matches in the URL and then in the notes.
+///
+/// Alternatives also match tags that they are an ancestor of, such as `lang` for `lang/rust`, and
+/// alternatives that are not a known tag are also matched against tags that they are a prefix of
+/// or that are only a typo away, but such matches score lower than exact matches.
+///
+/// Scores are normalized to be in the range of 0.0 (no keyword matched) to 1.0 (all keywords
+/// matched with maximum weight), so that they can be compared against a fixed threshold. The
+/// bookmarks are returned in the same order as they were given.
+pub fn rank(bookmarks: Vec<Bookmark>, keywords: &[Vec<Tag>]) -> Vec<(Score, Bookmark)> {
+    let docs: Vec<Document> = bookmarks.iter().map(Document::new).collect();
+    let corpus = Corpus::new(&docs);
+    let vocabulary: HashSet<&String> =
+        docs.iter().flat_map(|d| d.field(Field::Tags).counts.keys()).collect();
+    let keywords: Vec<Keyword> = keywords.iter().map(|k| corpus.keyword(k, &vocabulary)).collect();
+
+    docs.iter().map(|doc| corpus.score(doc, &keywords)).zip(bookmarks).collect()
+}
+
+/// Relevance of a bookmark for a query, together with how much each keyword contributed to it
+#[derive(Debug, Clone)]
+pub struct Score {
+    pub value: f64,
+    pub keywords: Vec<KeywordScore>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeywordScore {
+    /// The keyword as it was given, with alternatives separated by `|`
+    pub keyword: String,
+    /// Inverse document frequency of the keyword, which is also the highest possible contribution
+    /// of the keyword to the score before normalization
+    pub idf: f64,
+    /// The term that matched the keyword best, if any
+    pub term: Option<TermScore>,
+    /// Contribution of the keyword to the score before normalization
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TermScore {
+    pub term: String,
+    pub kind: Match,
+    /// Fields of the bookmark that contain the term
+    pub fields: Vec<Field>,
+    /// Term frequency, weighted by field and normalized by field length
+    pub tf: f64,
+}
+
+/// A keyword of a query, with the terms it matches and how each term was matched, and the inverse
+/// document frequency of the keyword as a whole
+struct Keyword {
+    name: String,
+    terms: Vec<(String, Match)>,
+    idf: f64,
+}
+
+/// Statistics of all bookmarks that are ranked together
+struct Corpus<'a> {
+    docs: &'a [Document],
+    avg_len: [f64; 4],
+}
+
+impl<'a> Corpus<'a> {
+    fn new(docs: &'a [Document]) -> Corpus<'a> {
+        let count: f64 = docs.len().max(1) as f64;
+        let avg_len: [f64; 4] = Field::ALL.map(|field| {
+            let total: usize = docs.iter().map(|d| d.field(field).len).sum();
+            total as f64 / count
+        });
+
+        Corpus { docs, avg_len }
+    }
+
+    fn keyword(&self, alternatives: &[Tag], vocabulary: &HashSet<&String>) -> Keyword {
+        let mut terms: Vec<(String, Match)> = Vec::with_capacity(alternatives.len());
+        for alternative in alternatives {
+            let alternative: String = tag::fold(alternative.as_ref());
+            let alternative: &String = &alternative;
+            terms.push((alternative.clone(), Match::Exact));
+            let descendants = vocabulary.iter().filter(|t| tag::is_descendant(t, alternative));
+            terms.extend(descendants.map(|t| (t.to_string(), Match::Ancestor)));
+            if !vocabulary.contains(alternative) {
+                let expanded = fuzzy::expand(alternative, vocabulary.iter().copied());
+                let expanded: Vec<(String, Match)> = expanded
+                    .into_iter()
+                    .filter(|(t, _)| !tag::is_descendant(t, alternative))
+                    .collect();
+                terms.extend(expanded);
+            }
+        }
+        let idf: f64 = self.idf(&terms);
+        let name: String = alternatives.iter().join("|");
+        Keyword { name, terms, idf }
+    }
+
+    /// Inverse document frequency of a keyword, where a document is considered to contain the
+    /// keyword if it contains any of its terms
+    fn idf(&self, terms: &[(String, Match)]) -> f64 {
+        let n: f64 = self.docs.len() as f64;
+        let matching: f64 = self
+            .docs
+            .iter()
+            .filter(|d| terms.iter().any(|(term, _)| d.contains(term)))
+            .count() as f64;
+        (1.0 + (n - matching + 0.5) / (matching + 0.5)).ln()
+    }
+
+    fn score(&self, doc: &Document, keywords: &[Keyword]) -> Score {
+        let keywords: Vec<KeywordScore> =
+            keywords.iter().map(|k| self.score_keyword(doc, k)).collect();
+        let score: f64 = keywords.iter().map(|k| k.score).sum();
+        let max_score: f64 = keywords.iter().map(|k| k.idf).sum();
+        let value: f64 = if max_score > 0.0 { score / max_score } else { 0.0 };
+
+        Score { value, keywords }
+    }
+
+    /// Score the keyword by the term of it that matches the document best
+    fn score_keyword(&self, doc: &Document, keyword: &Keyword) -> KeywordScore {
+        let best: Option<(f64, TermScore)> = keyword
+            .terms
+            .iter()
+            .filter(|(term, _)| doc.contains(term))
+            .map(|(term, kind)| {
+                let tf: f64 = self.weighted_tf(doc, term);
+                let saturation: f64 = kind.factor() * tf / (K1 + tf);
+                let fields: Vec<Field> = Field::ALL
+                    .into_iter()
+                    .filter(|f| doc.field(*f).counts.contains_key(term))
+                    .collect();
+                let term = TermScore {
+                    term: term.clone(),
+                    kind: *kind,
+                    fields,
+                    tf,
+                };
+                (saturation, term)
+            })
+            .max_by(|(s0, _), (s1, _)| s0.partial_cmp(s1).unwrap());
+
+        let (saturation, term): (f64, Option<TermScore>) = match best {
+            Some((saturation, term)) => (saturation, Some(term)),
+            None => (0.0, None),
+        };
+
+        KeywordScore {
+            keyword: keyword.name.clone(),
+            idf: keyword.idf,
+            term,
+            score: keyword.idf * saturation,
+        }
+    }
+
+    /// Term frequency of the term in the document, weighted by field and normalized by the length
+    /// of each field relative to the average length of that field.
+    fn weighted_tf(&self, doc: &Document, term: &str) -> f64 {
+        Field::ALL
+            .iter()
+            .map(|field| {
+                let terms: &FieldTerms = doc.field(*field);
+                let count: f64 = *terms.counts.get(term).unwrap_or(&0) as f64;
+                if count == 0.0 {
+                    return 0.0;
+                }
+                let avg_len: f64 = self.avg_len[*field as usize].max(1.0);
+                let norm: f64 = 1.0 - B + B * (terms.len as f64 / avg_len);
+                field.weight() * count / norm
+            })
+            .sum()
+    }
+}
+
+/// Extract the terms of a field of a bookmark. Tags are used as they are, while the title and the
+/// host and path of the URL, and the description and notes are split into lowercase words.
+fn terms(bkm: &Bookmark, field: Field) -> Vec<String> {
+    match field {
+        Field::Tags => bkm.tags().iter().map(|t| tag::fold(t.as_ref())).collect(),
+        Field::Title => words(&bkm.title().unwrap_or_default()),
+        Field::Url => {
+            let url = bkm.url();
+            let host: Vec<String> = url
+                .host_str()
+                .unwrap_or_default()
+                .split('.')
+                .filter(|label| *label != "www")
+                .flat_map(words)
+                .collect();
+            host.into_iter().chain(words(url.path())).collect()
+        }
+        Field::Notes => {
+            let description: String = bkm.description().unwrap_or_default();
+            let notes: String = bkm.notes().unwrap_or_default();
+            words(&description).into_iter().chain(words(&notes)).collect()
+        }
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    WORD_SEPARATOR
+        .split(text)
+        .filter(|w| !w.is_empty())
+        .map(|w| tag::fold(&w.to_lowercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::make_bookmark;
+
+    fn keywords(keywords: &[&str]) -> Vec<Vec<Tag>> {
+        keywords.iter().filter_map(|t| Tag::new(*t).ok()).map(|t| vec![t]).collect()
+    }
+
+    #[test]
+    fn tag_match_ranks_above_title_match_above_url_match() {
+        let bookmarks = vec![
+            make_bookmark("https://example.com/rust", None, &[]),
+            make_bookmark("https://example.com", Some("Learn Rust"), &[]),
+            make_bookmark("https://example.com", None, &["rust"]),
+        ];
+        let scores: Vec<f64> = rank(bookmarks, &keywords(&["rust"]))
+            .into_iter()
+            .map(|(s, _)| s.value)
+            .collect();
+        assert!(scores[0] > 0.0);
+        assert!(scores[1] > scores[0]);
+        assert!(scores[2] > scores[1]);
+    }
+
+    #[test]
+    fn notes_match_ranks_below_url_match() {
+        let bookmarks = vec![
+            make_bookmark("https://example.com", None, &[])
+                .with_notes(Some(String::from("Use the staging credentials from vault"))),
+            make_bookmark("https://vault.example.com", None, &[]),
+        ];
+        let scores: Vec<f64> = rank(bookmarks, &keywords(&["vault"]))
+            .into_iter()
+            .map(|(s, _)| s.value)
+            .collect();
+        assert!(scores[0] > 0.0);
+        assert!(scores[1] > scores[0]);
+    }
+
+    #[test]
+    fn no_matching_keywords_gives_zero_score() {
+        let bookmarks = vec![make_bookmark(
+            "https://example.com",
+            Some("Example"),
+            &["docs"],
+        )];
+        let ranked = rank(bookmarks, &keywords(&["rust"]));
+        assert_eq!(ranked[0].0.value, 0.0);
+    }
+
+    #[test]
+    fn score_is_normalized() {
+        let bookmarks = vec![
+            make_bookmark("https://rust-lang.org/rust", Some("Rust rust"), &["rust"]),
+            make_bookmark("https://example.com", None, &[]),
+        ];
+        let ranked = rank(bookmarks, &keywords(&["rust"]));
+        assert!(ranked[0].0.value > 0.5);
+        assert!(ranked[0].0.value < 1.0);
+    }
+
+    #[test]
+    fn typo_matches_known_tag_with_lower_score() {
+        let bookmarks = vec![
+            make_bookmark("https://kubernetes.io", None, &["kubernetes"]),
+            make_bookmark("https://example.com", None, &["docs"]),
+        ];
+        let exact = rank(bookmarks.clone(), &keywords(&["kubernetes"]));
+        let typo = rank(bookmarks, &keywords(&["kubernete"]));
+        assert!(typo[0].0.value > 0.0);
+        assert!(typo[0].0.value < exact[0].0.value);
+        assert_eq!(typo[1].0.value, 0.0);
+    }
+
+    #[test]
+    fn ancestor_matches_descendant_tag_with_lower_score() {
+        let bookmarks = vec![
+            make_bookmark("https://example.com/a", None, &["lang"]),
+            make_bookmark("https://example.com/b", None, &["lang/rust"]),
+            make_bookmark("https://example.com/c", None, &["language"]),
+        ];
+        let ranked = rank(bookmarks, &keywords(&["lang"]));
+        assert!(ranked[1].0.value > 0.0);
+        assert!(ranked[1].0.value < ranked[0].0.value);
+        assert_eq!(ranked[2].0.value, 0.0);
+    }
+
+    #[test]
+    fn best_matching_alternative_is_used() {
+        let bookmarks = vec![
+            make_bookmark("https://go.dev", None, &["go"]),
+            make_bookmark("https://example.com", None, &["docs"]),
+        ];
+        let alternatives: Vec<Vec<Tag>> =
+            vec![vec![Tag::new("rust").unwrap(), Tag::new("go").unwrap()]];
+        let either = rank(bookmarks.clone(), &alternatives);
+        let exact = rank(bookmarks, &keywords(&["go"]));
+        assert_eq!(either[0].0.value, exact[0].0.value);
+        assert_eq!(either[1].0.value, 0.0);
+    }
+
+    #[test]
+    fn url_is_split_into_host_and_path_terms() {
+        let bkm = make_bookmark("https://www.github.com/rust-lang/rust_book", None, &[]);
+        let terms: Vec<String> = terms(&bkm, Field::Url);
+        assert_eq!(terms, vec!["github", "com", "rust", "lang", "rust", "book"]);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::tag::Tag;
+use crate::Error;
+
+/// A rule in the configuration file that tags bookmarks with matching URLs, such as
+///
+/// ```yaml
+/// rules:
+///   - glob: docs.rs/*
+///     tags: [rust, docs]
+///   - glob: "*.atlassian.net"
+///     tags: [jira, work]
+///   - regex: ^https://github\.com/[^/]+/[^/]+/issues
+///     tags: [issue]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleConfig {
+    #[serde(flatten)]
+    pattern: Pattern,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Pattern {
+    /// A glob where `*` matches any number of characters and `?` matches a single character. A
+    /// glob without a `/` is matched against the host of the URL, otherwise it is matched against
+    /// the host and path of the URL.
+    Glob(String),
+    /// A regular expression that is matched against the full URL
+    Regex(String),
+}
+
+/// Rules for tagging bookmarks by their URL
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    target: Target,
+    tags: HashSet<Tag>,
+}
+
+/// The part of the URL that the regex of a rule is matched against
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    Host,
+    HostAndPath,
+    Url,
+}
+
+impl Rules {
+    pub fn new(config: &[RuleConfig]) -> Result<Rules, Error> {
+        let rules: Vec<Rule> = config.iter().map(Rule::new).collect::<Result<_, _>>()?;
+        Ok(Rules { rules })
+    }
+
+    /// Tags of all rules that match the URL
+    pub fn tags(&self, url: &Url) -> HashSet<Tag> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(url))
+            .flat_map(|rule| rule.tags.iter().cloned())
+            .collect()
+    }
+}
+
+impl Rule {
+    fn new(config: &RuleConfig) -> Result<Rule, Error> {
+        let (pattern, target): (String, Target) = match &config.pattern {
+            Pattern::Glob(glob) if glob.contains('/') => (glob_to_regex(glob), Target::HostAndPath),
+            Pattern::Glob(glob) => (glob_to_regex(glob), Target::Host),
+            Pattern::Regex(regex) => (regex.clone(), Target::Url),
+        };
+        let regex: Regex = Regex::new(&pattern).map_err(|e| {
+            let msg = format!("Invalid pattern '{}' in rule: {}", pattern, e);
+            Error::Config(msg)
+        })?;
+        let tags: HashSet<Tag> = config
+            .tags
+            .iter()
+            .filter_map(|tag| Tag::new(tag.as_str()).ok())
+            .map(Tag::canonical)
+            .collect();
+
+        Ok(Rule {
+            regex,
+            target,
+            tags,
+        })
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host: &str = url.host_str().unwrap_or_default();
+        match self.target {
+            Target::Host => self.regex.is_match(host),
+            Target::HostAndPath => self.regex.is_match(&format!("{}{}", host, url.path())),
+            Target::Url => self.regex.is_match(url.as_str()),
+        }
+    }
+}
+
+/// Convert a glob to a regex that must match the whole text
+fn glob_to_regex(glob: &str) -> String {
+    let pattern: String = glob
+        .split('*')
+        .map(|part| part.split('?').map(regex::escape).collect::<Vec<String>>().join("."))
+        .collect::<Vec<String>>()
+        .join(".*");
+
+    format!("^(?:{})$", pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(yaml: &str) -> Rules {
+        let config: Vec<RuleConfig> = serde_yaml::from_str(yaml).unwrap();
+        Rules::new(&config).unwrap()
+    }
+
+    fn tags(url: &str, rules: &Rules) -> Vec<String> {
+        let url = Url::parse(url).unwrap();
+        let mut tags: Vec<String> = rules.tags(&url).iter().map(|t| t.to_string()).collect();
+        tags.sort();
+        tags
+    }
+
+    #[test]
+    fn glob_without_slash_matches_host() {
+        let rules: Rules = rules("- glob: '*.atlassian.net'\n  tags: [jira, work]");
+        assert_eq!(tags("https://acme.atlassian.net/browse/X-1", &rules), vec!["jira", "work"]);
+        assert!(tags("https://atlassian.net", &rules).is_empty());
+    }
+
+    #[test]
+    fn glob_with_slash_matches_host_and_path() {
+        let rules: Rules = rules("- glob: docs.rs/*\n  tags: [rust, docs]");
+        assert_eq!(tags("https://docs.rs/serde/latest/serde/", &rules), vec!["docs", "rust"]);
+        assert!(tags("https://crates.io/docs.rs/", &rules).is_empty());
+    }
+
+    #[test]
+    fn regex_matches_full_url() {
+        let rules: Rules = rules("- regex: ^https://github\\.com/.+/issues\n  tags: [issue]");
+        assert_eq!(tags("https://github.com/a/b/issues/1", &rules), vec!["issue"]);
+        assert!(tags("http://github.com/a/b/issues/1", &rules).is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::Error;
+
+/// Placeholder in the URL template of a search engine that is replaced with the search query
+const QUERY: &str = "{query}";
+
+/// Characters that are percent-encoded in a search query, which is everything except the
+/// characters that are never reserved in a URL
+const ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// Search engines that are always available, unless overridden in the configuration file
+const BUILTIN: [(&str, &str); 6] = [
+    ("duckduckgo", "https://duckduckgo.com/?q={query}"),
+    ("google", "https://www.google.com/search?q={query}"),
+    ("gh", "https://github.com/search?q={query}"),
+    ("crates", "https://crates.io/search?q={query}"),
+    ("docs", "https://docs.rs/releases/search?query={query}"),
+    ("w", "https://en.wikipedia.org/w/index.php?search={query}"),
+];
+
+/// Search engines used when no bookmark matches the keywords given to `goto open`, configured as
+///
+/// ```yaml
+/// search:
+///   default: duckduckgo
+///   engines:
+///     mdn: https://developer.mozilla.org/en-US/search?q={query}
+/// ```
+///
+/// A specific engine can be selected with a bang, such as `goto open !mdn fetch`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Engines {
+    /// Name of the engine to use when none is selected with a bang
+    pub default: String,
+    /// URL templates of engines by name, in addition to the built-in engines
+    pub engines: BTreeMap<String, String>,
+}
+
+impl Default for Engines {
+    fn default() -> Self {
+        Engines {
+            default: String::from("duckduckgo"),
+            engines: BTreeMap::new(),
+        }
+    }
+}
+
+impl Engines {
+    /// URL template of the engine with the given name, either from the configuration or built-in
+    fn template(&self, name: &str) -> Option<&str> {
+        match self.engines.get(name) {
+            Some(template) => Some(template.as_str()),
+            None => BUILTIN.iter().find(|(n, _)| *n == name).map(|(_, template)| *template),
+        }
+    }
+
+    /// Find a bang among the keywords, such as `!gh`, that names a known engine. If there is one,
+    /// return the name of the engine together with the remaining keywords.
+    pub fn bang<'a>(&self, keywords: &'a [String]) -> Option<(&'a str, Vec<&'a str>)> {
+        let position: usize = keywords.iter().position(|k| match k.strip_prefix('!') {
+            Some(name) => self.template(name).is_some(),
+            None => false,
+        })?;
+        let name: &str = &keywords[position][1..];
+        let rest: Vec<&str> = keywords
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != position)
+            .map(|(_, k)| k.as_str())
+            .collect();
+
+        Some((name, rest))
+    }
+
+    /// URL for searching for the keywords with the engine with the given name, or with the default
+    /// engine if no name is given
+    pub fn url<T: AsRef<str>>(&self, name: Option<&str>, keywords: &[T]) -> Result<Url, Error> {
+        let name: &str = name.unwrap_or(&self.default);
+        let template: &str = match self.template(name) {
+            Some(template) => template,
+            None => {
+                let msg = format!("Unknown search engine '{}'", name);
+                return Err(Error::Config(msg));
+            }
+        };
+
+        let query: String = keywords.iter().map(|k| k.as_ref()).collect::<Vec<&str>>().join(" ");
+        let query: String = utf8_percent_encode(&query, ENCODE).to_string();
+        let url: String = template.replace(QUERY, &query);
+        Url::parse(&url).map_err(|e| {
+            let msg = format!("Invalid URL '{}' for search engine '{}': {}", template, name, e);
+            Error::Config(msg)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(keywords: &[&str]) -> Vec<String> {
+        keywords.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn default_engine_is_used_without_bang() {
+        let url: Url = Engines::default().url(None, &["rust", "lifetimes"]).unwrap();
+        assert_eq!(url.as_str(), "https://duckduckgo.com/?q=rust%20lifetimes");
+    }
+
+    #[test]
+    fn query_is_percent_encoded() {
+        let url: Url = Engines::default().url(Some("gh"), &["a&b=c", "#1", "ö"]).unwrap();
+        assert_eq!(url.as_str(), "https://github.com/search?q=a%26b%3Dc%20%231%20%C3%B6");
+    }
+
+    #[test]
+    fn bang_selects_engine_and_is_removed_from_keywords() {
+        let engines = Engines::default();
+        let keywords: Vec<String> = keywords(&["serde", "!crates"]);
+        assert_eq!(engines.bang(&keywords), Some(("crates", vec!["serde"])));
+    }
+
+    #[test]
+    fn unknown_bang_is_kept_as_keyword() {
+        let engines = Engines::default();
+        assert_eq!(engines.bang(&keywords(&["!nope", "foo"])), None);
+    }
+
+    #[test]
+    fn configured_engine_overrides_builtin() {
+        let mut engines = Engines::default();
+        engines
+            .engines
+            .insert(String::from("gh"), String::from("https://ghe.example.com/s/{query}"));
+        let url: Url = engines.url(Some("gh"), &["x y"]).unwrap();
+        assert_eq!(url.as_str(), "https://ghe.example.com/s/x%20y");
+    }
+}
@@ -0,0 +1,150 @@
+mod layered;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::bookmark::{self, Bookmark, FileError};
+use crate::index::Index;
+use crate::time;
+
+/// Persistence of bookmarks, independent of how and where they are stored
+pub trait Storage {
+    /// All stored bookmarks, together with the path of the file they are stored in
+    fn load_all(&self) -> Result<Vec<(PathBuf, Bookmark)>, FileError>;
+
+    fn get(&self, url: &Url) -> Result<Option<Bookmark>, FileError>;
+
+    /// The bookmark with the URL as it is stored in this storage, without anything merged into it
+    /// from read-only sources. This is the bookmark that changes are written to.
+    fn get_own(&self, url: &Url) -> Result<Option<Bookmark>, FileError> {
+        self.get(url)
+    }
+
+    /// The bookmark with the URL as it is read from the read-only sources only, or `None` if no
+    /// source has a bookmark with the URL
+    fn get_sourced(&self, _url: &Url) -> Result<Option<Bookmark>, FileError> {
+        Ok(None)
+    }
+
+    /// Write the bookmark as is, replacing any existing bookmark with the same URL
+    fn write(&self, bkm: &Bookmark) -> Result<(), FileError>;
+
+    fn delete(&self, bkm: &Bookmark) -> Result<(), FileError>;
+
+    /// Name of the read-only source the bookmark with the URL is read from, or `None` if the
+    /// bookmark is stored in this storage and can be modified
+    fn source(&self, _url: &Url) -> Result<Option<&str>, FileError> {
+        Ok(None)
+    }
+
+    /// Save the bookmark, and if `merge` is true, merge it with any existing bookmark with the
+    /// same URL first. The bookmark is marked as modified now, and keeps the creation time of any
+    /// existing bookmark. Returns the bookmark as it was saved.
+    fn save(&self, bkm: Bookmark, merge: bool) -> Result<Bookmark, FileError> {
+        let prior_bkm: Option<Bookmark> = self.get(&bkm.url())?;
+        let bkm: Bookmark = bkm.touch(prior_bkm.as_ref(), time::now());
+        let bkm: Bookmark = match prior_bkm {
+            Some(prior_bkm) if merge => bkm.merge(prior_bkm),
+            _ => bkm,
+        };
+        self.write(&bkm)?;
+        Ok(bkm)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Yaml,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "yaml" => Ok(Kind::Yaml),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(Kind::Sqlite),
+            _ => Err(format!("Unrecognized storage {}", s)),
+        }
+    }
+}
+
+pub fn open(kind: Kind, dir: &Path) -> Result<Box<dyn Storage>, FileError> {
+    match kind {
+        Kind::Yaml => Ok(Box::new(YamlStorage::new(dir))),
+        #[cfg(feature = "sqlite")]
+        Kind::Sqlite => Ok(Box::new(sqlite::SqliteStorage::open(dir)?)),
+    }
+}
+
+/// Overlay read-only sources, given as directories of YAML bookmark files by name, on the
+/// storage in the data directory `dir`. Bookmarks from the sources are read along with the
+/// bookmarks of the storage, but any change is written to the storage only.
+pub fn with_sources(
+    storage: Box<dyn Storage>,
+    dir: &Path,
+    sources: BTreeMap<String, PathBuf>,
+) -> Box<dyn Storage> {
+    if sources.is_empty() {
+        storage
+    } else {
+        Box::new(layered::LayeredStorage::new(storage, dir, sources))
+    }
+}
+
+/// Stores each bookmark in its own YAML file, under `[DIR]/[DOMAIN]/[HASH_OF_URL].yaml`
+pub struct YamlStorage {
+    dir: PathBuf,
+}
+
+impl YamlStorage {
+    pub fn new(dir: &Path) -> YamlStorage {
+        YamlStorage {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+impl Storage for YamlStorage {
+    fn load_all(&self) -> Result<Vec<(PathBuf, Bookmark)>, FileError> {
+        let bookmarks = Index::open(&self.dir)
+            .bookmarks()
+            .map(|(path, bkm)| (path, bkm.clone()))
+            .collect();
+
+        Ok(bookmarks)
+    }
+
+    fn get(&self, url: &Url) -> Result<Option<Bookmark>, FileError> {
+        let full_path: PathBuf = self.dir.join(bookmark::rel_path(url));
+        if full_path.exists() {
+            Bookmark::from_file(&full_path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn write(&self, bkm: &Bookmark) -> Result<(), FileError> {
+        let full_path: PathBuf = self.dir.join(bkm.rel_path());
+        std::fs::create_dir_all(full_path.parent().expect("Create full path"))?;
+        let yaml: String = bkm.to_yaml()?;
+        std::fs::write(full_path, yaml)?;
+        Ok(())
+    }
+
+    fn delete(&self, bkm: &Bookmark) -> Result<(), FileError> {
+        let full_path: PathBuf = self.dir.join(bkm.rel_path());
+        std::fs::remove_file(full_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use super::Storage;
+use crate::bookmark::{Bookmark, FileError};
+use crate::index::Index;
+
+/// A storage with read-only sources overlaid on it, such as a directory of bookmarks that is
+/// shared by a team. Bookmarks with the same URL are merged, where the bookmark of the storage
+/// takes precedence over the bookmarks of the sources, and a source takes precedence over the
+/// sources after it in order of name.
+pub struct LayeredStorage {
+    storage: Box<dyn Storage>,
+    sources: Vec<Source>,
+}
+
+struct Source {
+    name: String,
+    dir: PathBuf,
+    /// Data directory of the storage, where the index of the source is kept
+    data_dir: PathBuf,
+    /// Bookmarks of the source by URL, read once when first needed. Files in a source are not
+    /// necessarily named the way goto names them, so bookmarks are always looked up by URL.
+    bookmarks: OnceCell<HashMap<Url, (PathBuf, Bookmark)>>,
+}
+
+impl Source {
+    fn bookmarks(&self) -> &HashMap<Url, (PathBuf, Bookmark)> {
+        self.bookmarks.get_or_init(|| {
+            Index::open_source(&self.data_dir, &self.name, &self.dir)
+                .bookmarks()
+                .map(|(path, bkm)| (bkm.url(), (path, bkm.clone())))
+                .collect()
+        })
+    }
+}
+
+impl LayeredStorage {
+    pub fn new(
+        storage: Box<dyn Storage>,
+        data_dir: &Path,
+        sources: BTreeMap<String, PathBuf>,
+    ) -> LayeredStorage {
+        let sources: Vec<Source> = sources
+            .into_iter()
+            .map(|(name, dir)| Source {
+                name,
+                dir,
+                data_dir: data_dir.to_path_buf(),
+                bookmarks: OnceCell::new(),
+            })
+            .collect();
+
+        LayeredStorage { storage, sources }
+    }
+}
+
+impl Storage for LayeredStorage {
+    fn load_all(&self) -> Result<Vec<(PathBuf, Bookmark)>, FileError> {
+        let mut bookmarks: Vec<(PathBuf, Bookmark)> = self.storage.load_all()?;
+        let mut positions: HashMap<Url, usize> =
+            bookmarks.iter().enumerate().map(|(i, (_, bkm))| (bkm.url(), i)).collect();
+
+        for source in &self.sources {
+            let mut entries: Vec<&(PathBuf, Bookmark)> = source.bookmarks().values().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for (path, bkm) in entries {
+                match positions.get(&bkm.url()) {
+                    Some(i) => {
+                        let prior: Bookmark = bookmarks[*i].1.clone();
+                        bookmarks[*i].1 = prior.merge(bkm.clone());
+                    }
+                    None => {
+                        positions.insert(bkm.url(), bookmarks.len());
+                        bookmarks.push((path.clone(), bkm.clone()));
+                    }
+                }
+            }
+        }
+
+        Ok(bookmarks)
+    }
+
+    fn get(&self, url: &Url) -> Result<Option<Bookmark>, FileError> {
+        let bookmark: Option<Bookmark> = match (self.storage.get(url)?, self.get_sourced(url)?) {
+            (Some(own), Some(sourced)) => Some(own.merge(sourced)),
+            (own, sourced) => own.or(sourced),
+        };
+
+        Ok(bookmark)
+    }
+
+    fn get_own(&self, url: &Url) -> Result<Option<Bookmark>, FileError> {
+        self.storage.get(url)
+    }
+
+    fn get_sourced(&self, url: &Url) -> Result<Option<Bookmark>, FileError> {
+        let mut bookmark: Option<Bookmark> = None;
+        for source in &self.sources {
+            if let Some((_, bkm)) = source.bookmarks().get(url) {
+                bookmark = match bookmark {
+                    Some(prior) => Some(prior.merge(bkm.clone())),
+                    None => Some(bkm.clone()),
+                };
+            }
+        }
+
+        Ok(bookmark)
+    }
+
+    fn write(&self, bkm: &Bookmark) -> Result<(), FileError> {
+        self.storage.write(bkm)
+    }
+
+    fn delete(&self, bkm: &Bookmark) -> Result<(), FileError> {
+        self.storage.delete(bkm)
+    }
+
+    fn source(&self, url: &Url) -> Result<Option<&str>, FileError> {
+        if self.storage.get(url)?.is_some() {
+            return Ok(None);
+        }
+        for source in &self.sources {
+            if source.bookmarks().contains_key(url) {
+                return Ok(Some(&source.name));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::{make_bookmark, temp_dir};
+    use crate::storage::YamlStorage;
+
+    fn storage(name: &str) -> LayeredStorage {
+        let personal: PathBuf = temp_dir(&format!("layered-{}-personal", name));
+        let team: PathBuf = temp_dir(&format!("layered-{}-team", name));
+        YamlStorage::new(&personal)
+            .write(&make_bookmark("https://example.com", Some("Mine"), &["rust"]))
+            .unwrap();
+        let team_storage = YamlStorage::new(&team);
+        team_storage
+            .write(&make_bookmark("https://example.com", Some("Theirs"), &["docs"]))
+            .unwrap();
+        team_storage
+            .write(&make_bookmark("https://team.example.com", None, &["ci"]))
+            .unwrap();
+
+        let sources = BTreeMap::from([(String::from("team"), team)]);
+        LayeredStorage::new(Box::new(YamlStorage::new(&personal)), &personal, sources)
+    }
+
+    #[test]
+    fn personal_bookmark_is_merged_with_source() {
+        let storage: LayeredStorage = storage("merge");
+        let bookmarks: Vec<(PathBuf, Bookmark)> = storage.load_all().unwrap();
+        assert_eq!(bookmarks.len(), 2);
+
+        let bkm: &Bookmark = &bookmarks[0].1;
+        assert_eq!(bkm, &make_bookmark("https://example.com", None, &["rust", "docs"]));
+        assert_eq!(bkm.title(), Some(String::from("Mine")));
+    }
+
+    #[test]
+    fn source_is_only_reported_for_bookmarks_not_in_storage() {
+        let storage: LayeredStorage = storage("source");
+        let personal = Url::parse("https://example.com").unwrap();
+        let team = Url::parse("https://team.example.com").unwrap();
+        assert_eq!(storage.source(&personal).unwrap(), None);
+        assert_eq!(storage.source(&team).unwrap(), Some("team"));
+    }
+
+    #[test]
+    fn index_of_source_is_kept_in_data_directory() {
+        let storage: LayeredStorage = storage("index");
+        storage.load_all().unwrap();
+        let source: &Source = &storage.sources[0];
+        assert!(source.data_dir.join(".index/sources/team.json").is_file());
+        assert!(!source.dir.join(".index").exists());
+    }
+}
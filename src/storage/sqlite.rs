@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use url::Url;
+
+use super::Storage;
+use crate::bookmark::{Bookmark, FileError};
+use crate::tag::Tag;
+
+/// Name of the database file in the data directory. It is hidden so that it is not mistaken for a
+/// bookmark file if the directory is also used with the YAML storage.
+const DATABASE_FILE: &str = ".bookmarks.sqlite";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS bookmarks (
+        url TEXT PRIMARY KEY NOT NULL,
+        title TEXT,
+        description TEXT,
+        notes TEXT,
+        created INTEGER,
+        modified INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS tags (
+        url TEXT NOT NULL REFERENCES bookmarks(url) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (url, tag)
+    );
+    CREATE INDEX IF NOT EXISTS tags_by_tag ON tags(tag);
+";
+
+/// Columns that were added to the bookmarks table after it was first created, which are added to
+/// databases created before them
+const ADDED_COLUMNS: [(&str, &str); 4] = [
+    ("created", "INTEGER"),
+    ("modified", "INTEGER"),
+    ("description", "TEXT"),
+    ("notes", "TEXT"),
+];
+
+/// Columns of the bookmarks table that are read by [read_row]
+const COLUMNS: &str = "url, title, description, notes, created, modified";
+
+/// Stores all bookmarks in a single SQLite database in the data directory
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(dir: &Path) -> Result<SqliteStorage, FileError> {
+        std::fs::create_dir_all(dir)?;
+        let path: PathBuf = dir.join(DATABASE_FILE);
+        let conn = Connection::open(&path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        add_missing_columns(&conn)?;
+        Ok(SqliteStorage { conn })
+    }
+
+    fn tags(&self, url: &str) -> Result<HashSet<Tag>, FileError> {
+        let mut stmt = self.conn.prepare_cached("SELECT tag FROM tags WHERE url = ?1")?;
+        let tags = stmt
+            .query_map([url], |row| row.get::<_, String>(0))?
+            .filter_map(|tag| tag.ok())
+            .filter_map(|tag| Tag::new(tag).ok())
+            .collect();
+
+        Ok(tags)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_all(&self) -> Result<Vec<(PathBuf, Bookmark)>, FileError> {
+        let mut tags: HashMap<String, HashSet<Tag>> = HashMap::new();
+        let mut stmt = self.conn.prepare("SELECT url, tag FROM tags")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?;
+        for row in rows {
+            let (url, tag): (String, String) = row?;
+            if let Ok(tag) = Tag::new(tag) {
+                tags.entry(url).or_default().insert(tag);
+            }
+        }
+
+        let query: String = format!("SELECT {} FROM bookmarks ORDER BY url", COLUMNS);
+        let mut stmt = self.conn.prepare(&query)?;
+        // Bookmarks in the database have no file of their own, so their path is empty
+        let mut bookmarks: Vec<(PathBuf, Bookmark)> = Vec::new();
+        for row in stmt.query_map([], read_row)? {
+            let (url, bkm) = row?;
+            let bkm_tags: HashSet<Tag> = tags.remove(&url).unwrap_or_default();
+            match bkm {
+                Some(bkm) => bookmarks.push((PathBuf::new(), bkm.with_tags(bkm_tags))),
+                None => log::error!("Invalid URL in database: {}", url),
+            }
+        }
+
+        Ok(bookmarks)
+    }
+
+    fn get(&self, url: &Url) -> Result<Option<Bookmark>, FileError> {
+        let query: String = format!("SELECT {} FROM bookmarks WHERE url = ?1", COLUMNS);
+        let row = self.conn.query_row(&query, [url.as_str()], read_row).optional()?;
+
+        match row {
+            Some((_, Some(bkm))) => {
+                let tags: HashSet<Tag> = self.tags(url.as_str())?;
+                Ok(Some(bkm.with_tags(tags)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn write(&self, bkm: &Bookmark) -> Result<(), FileError> {
+        let url: String = bkm.url().to_string();
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO bookmarks (url, title, description, notes, created, modified)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(url) DO UPDATE SET title = excluded.title,
+             description = excluded.description, notes = excluded.notes,
+             created = excluded.created, modified = excluded.modified",
+            params![
+                url,
+                bkm.title(),
+                bkm.description(),
+                bkm.notes(),
+                to_secs(bkm.created()),
+                to_secs(bkm.modified())
+            ],
+        )?;
+        tx.execute("DELETE FROM tags WHERE url = ?1", [&url])?;
+        for tag in bkm.tags() {
+            tx.execute(
+                "INSERT INTO tags (url, tag) VALUES (?1, ?2)",
+                params![url, tag.to_string()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, bkm: &Bookmark) -> Result<(), FileError> {
+        let deleted: usize = self
+            .conn
+            .execute("DELETE FROM bookmarks WHERE url = ?1", [bkm.url().as_str()])?;
+        match deleted {
+            0 => Err(FileError::NotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Read a row of [COLUMNS] as the URL and the bookmark without tags, which is `None` if the URL is
+/// not valid
+fn read_row(row: &Row) -> rusqlite::Result<(String, Option<Bookmark>)> {
+    let url: String = row.get(0)?;
+    let bkm: Option<Bookmark> = Bookmark::new(url.as_str(), row.get(1)?, HashSet::new())
+        .ok()
+        .map(|bkm| -> rusqlite::Result<Bookmark> {
+            let created: Option<u64> = row.get(4)?;
+            let modified: Option<u64> = row.get(5)?;
+            Ok(bkm
+                .with_description(row.get(2)?)
+                .with_notes(row.get(3)?)
+                .with_timestamps(from_secs(created), from_secs(modified)))
+        })
+        .transpose()?;
+
+    Ok((url, bkm))
+}
+
+/// Add the columns in [ADDED_COLUMNS] that are missing from databases created before them
+fn add_missing_columns(conn: &Connection) -> Result<(), FileError> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('bookmarks')")?;
+    let columns: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+    for (name, kind) in ADDED_COLUMNS {
+        if !columns.iter().any(|c| c == name) {
+            conn.execute_batch(&format!("ALTER TABLE bookmarks ADD COLUMN {} {};", name, kind))?;
+        }
+    }
+    Ok(())
+}
+
+/// Timestamps are stored as seconds since the Unix epoch
+fn to_secs(time: Option<SystemTime>) -> Option<u64> {
+    time.and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs())
+}
+
+fn from_secs(secs: Option<u64>) -> Option<SystemTime> {
+    secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+impl From<rusqlite::Error> for FileError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Database(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::{make_bookmark, temp_dir};
+
+    fn storage(name: &str) -> SqliteStorage {
+        SqliteStorage::open(&temp_dir(name)).unwrap()
+    }
+
+    #[test]
+    fn save_merges_tags_with_existing_bookmark() {
+        let storage: SqliteStorage = storage("sqlite-merge");
+        storage
+            .save(make_bookmark("https://example.com", Some("T"), &["rust"]), true)
+            .unwrap();
+        storage
+            .save(make_bookmark("https://example.com", Some("T"), &["docs"]), true)
+            .unwrap();
+
+        let url = Url::parse("https://example.com").unwrap();
+        let bkm: Bookmark = storage.get(&url).unwrap().unwrap();
+        assert_eq!(bkm, make_bookmark("https://example.com", None, &["rust", "docs"]));
+        assert_eq!(bkm.title(), Some(String::from("T")));
+    }
+
+    #[test]
+    fn delete_removes_bookmark_and_tags() {
+        let storage: SqliteStorage = storage("sqlite-delete");
+        let bkm: Bookmark = make_bookmark("https://example.com", None, &["rust"]);
+        storage.save(bkm.clone(), false).unwrap();
+        assert_eq!(storage.load_all().unwrap(), vec![(PathBuf::new(), bkm.clone())]);
+        storage.delete(&bkm).unwrap();
+
+        assert!(storage.load_all().unwrap().is_empty());
+        assert!(storage.tags("https://example.com/").unwrap().is_empty());
+    }
+}
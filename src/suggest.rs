@@ -0,0 +1,156 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use url::Url;
+
+use crate::bookmark::Bookmark;
+use crate::tag::Tag;
+
+/// Most tags that are suggested at once
+const MAX_SUGGESTIONS: usize = 10;
+
+/// Least share of the related bookmarks that must have a tag for it to be suggested
+const MIN_SHARE: f64 = 0.2;
+
+/// A tag that is suggested for a bookmark, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub tag: Tag,
+    pub reason: Reason,
+    /// Share of the related bookmarks that have the tag, from 0.0 to 1.0
+    share: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The tag is often used together with the tags that were already given
+    CoOccurrence,
+    /// The tag is used by other bookmarks on the same domain
+    Domain,
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent: usize = (self.share * 100.0).round() as usize;
+        match self.reason {
+            Reason::CoOccurrence => {
+                write!(f, "{} ({}% of bookmarks with these tags)", self.tag, percent)
+            }
+            Reason::Domain => write!(f, "{} ({}% of bookmarks on this domain)", self.tag, percent),
+        }
+    }
+}
+
+/// Suggest tags for a bookmark with the URL and tags, from tags that other bookmarks in the
+/// collection with any of the same tags or on the same domain have. Tags that the bookmark already
+/// has are never suggested. The best suggestions come first.
+pub fn suggest<'a>(
+    bookmarks: impl IntoIterator<Item = &'a Bookmark>,
+    url: &Url,
+    tags: &HashSet<Tag>,
+) -> Vec<Suggestion> {
+    let domain: Option<&str> = url.domain();
+    let mut co_occurrence = Counter::default();
+    let mut same_domain = Counter::default();
+
+    for bkm in bookmarks {
+        if &bkm.url() == url {
+            continue;
+        }
+        if !bkm.tags().is_disjoint(tags) {
+            co_occurrence.add(bkm.tags());
+        }
+        if domain.is_some() && bkm.domain() == domain {
+            same_domain.add(bkm.tags());
+        }
+    }
+
+    let mut suggestions: Vec<Suggestion> = co_occurrence
+        .shares(Reason::CoOccurrence)
+        .chain(same_domain.shares(Reason::Domain))
+        .filter(|s| s.share >= MIN_SHARE && !tags.contains(&s.tag))
+        .collect();
+
+    suggestions.sort_by(|a, b| b.share.partial_cmp(&a.share).unwrap().then(a.tag.cmp(&b.tag)));
+    let mut seen: HashSet<Tag> = HashSet::new();
+    suggestions.retain(|s| seen.insert(s.tag.clone()));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+/// Number of related bookmarks with each tag
+#[derive(Default)]
+struct Counter {
+    bookmarks: usize,
+    tags: HashMap<Tag, usize>,
+}
+
+impl Counter {
+    fn add(&mut self, tags: &HashSet<Tag>) {
+        self.bookmarks += 1;
+        for tag in tags {
+            *self.tags.entry(tag.clone()).or_default() += 1;
+        }
+    }
+
+    fn shares(self, reason: Reason) -> impl Iterator<Item = Suggestion> {
+        let total: f64 = self.bookmarks.max(1) as f64;
+        self.tags.into_iter().map(move |(tag, count)| Suggestion {
+            tag,
+            reason,
+            share: count as f64 / total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::make_bookmark;
+
+    fn tags(tags: &[&str]) -> HashSet<Tag> {
+        tags.iter().filter_map(|t| Tag::new(*t).ok()).collect()
+    }
+
+    fn suggested(suggestions: &[Suggestion]) -> Vec<(String, Reason)> {
+        suggestions.iter().map(|s| (s.tag.to_string(), s.reason)).collect()
+    }
+
+    #[test]
+    fn suggests_tags_that_co_occur() {
+        let bookmarks = vec![
+            make_bookmark("https://a.com", None, &["rust", "async"]),
+            make_bookmark("https://b.com", None, &["rust", "async", "tokio"]),
+            make_bookmark("https://c.com", None, &["go"]),
+        ];
+        let url = Url::parse("https://new.com").unwrap();
+        let suggestions = suggest(&bookmarks, &url, &tags(&["rust"]));
+        let expected = vec![
+            (String::from("async"), Reason::CoOccurrence),
+            (String::from("tokio"), Reason::CoOccurrence),
+        ];
+        assert_eq!(suggested(&suggestions), expected);
+    }
+
+    #[test]
+    fn suggests_tags_on_same_domain() {
+        let bookmarks = vec![
+            make_bookmark("https://docs.rs/serde", None, &["rust", "docs"]),
+            make_bookmark("https://crates.io/serde", None, &["crates"]),
+        ];
+        let url = Url::parse("https://docs.rs/tokio").unwrap();
+        let suggestions = suggest(&bookmarks, &url, &tags(&[]));
+        let expected = vec![
+            (String::from("docs"), Reason::Domain),
+            (String::from("rust"), Reason::Domain),
+        ];
+        assert_eq!(suggested(&suggestions), expected);
+    }
+
+    #[test]
+    fn suggests_nothing_for_unrelated_bookmark() {
+        let bookmarks = vec![make_bookmark("https://a.com", None, &["rust"])];
+        let url = Url::parse("https://b.com").unwrap();
+        assert!(suggest(&bookmarks, &url, &tags(&["go"])).is_empty());
+    }
+}
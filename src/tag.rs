@@ -2,20 +2,152 @@ use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
 use std::{fmt::Display, hash::Hash, str::FromStr};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
 lazy_static! {
     static ref TERMINATOR: Regex = Regex::new(r"[,\s]+").unwrap();
     static ref DISCARD: Regex = Regex::new(r#"[,\s"\\]+"#).unwrap();
 }
 
+/// Aliases of tags, which are set once from the configuration before any tags are read
+static ALIASES: OnceLock<Aliases> = OnceLock::new();
+
+#[derive(Debug, Default)]
+struct Aliases {
+    /// Canonical tag of each alias
+    canonical: HashMap<Tag, Tag>,
+    /// All aliases of each canonical tag
+    aliases: HashMap<Tag, Vec<Tag>>,
+}
+
+impl Aliases {
+    fn new(config: &BTreeMap<String, Vec<String>>) -> Aliases {
+        let mut aliases = Aliases::default();
+        for (canonical, config_aliases) in config {
+            let canonical: Tag = match Tag::new(canonical.as_str()) {
+                Ok(tag) => tag,
+                Err(_) => continue,
+            };
+            for alias in config_aliases.iter().filter_map(|a| Tag::new(a.as_str()).ok()) {
+                if alias == canonical {
+                    continue;
+                }
+                if let Some(other) = aliases.canonical.insert(alias.clone(), canonical.clone()) {
+                    log::warn!(
+                        "Alias {} is configured for both {} and {}",
+                        alias,
+                        other,
+                        canonical
+                    );
+                }
+                aliases.aliases.entry(canonical.clone()).or_default().push(alias);
+            }
+        }
+        aliases
+    }
+
+    fn canonical(&self, tag: Tag) -> Tag {
+        match self.canonical.get(&tag) {
+            Some(canonical) => canonical.clone(),
+            None => tag,
+        }
+    }
+
+    fn synonyms(&self, tag: &Tag) -> Vec<Tag> {
+        let canonical: Tag = self.canonical(tag.clone());
+        let aliases: &[Tag] =
+            self.aliases.get(&canonical).map(|a| a.as_slice()).unwrap_or_default();
+        std::iter::once(canonical).chain(aliases.iter().cloned()).collect()
+    }
+}
+
+/// Whether diacritics are ignored when matching tags and keywords, which is set once from the
+/// configuration
+static FOLD_DIACRITICS: OnceLock<bool> = OnceLock::new();
+
+/// Set whether diacritics are ignored when matching, so that `cafe` matches `café`
+pub fn set_fold_diacritics(fold: bool) {
+    if FOLD_DIACRITICS.set(fold).is_err() {
+        log::warn!("Folding of diacritics was already set");
+    }
+}
+
+/// Fold the text for matching if folding of diacritics is enabled, otherwise return it as it is
+pub fn fold(text: &str) -> String {
+    match FOLD_DIACRITICS.get() {
+        Some(true) => fold_diacritics(text),
+        _ => text.to_string(),
+    }
+}
+
+/// Remove all diacritics from the text, such as `café` to `cafe`
+fn fold_diacritics(text: &str) -> String {
+    text.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect()
+}
+
+/// Check if the character is invisible, such as a zero-width space, which would make tags that
+/// look the same differ
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Check if the character at `index` is a zero-width joiner between two emoji, such as in 👩‍💻,
+/// where it is part of the emoji rather than an invisible character. Variation selectors and skin
+/// tones after the first emoji are skipped.
+fn joins_emoji(chars: &[char], index: usize) -> bool {
+    let is_emoji = |c: &char| {
+        matches!(
+            c,
+            '\u{2300}'..='\u{23FF}'
+                | '\u{2600}'..='\u{27BF}'
+                | '\u{2B00}'..='\u{2BFF}'
+                | '\u{1F000}'..='\u{1FAFF}'
+        )
+    };
+    let is_modifier = |c: &&char| matches!(c, '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}');
+    chars[index] == '\u{200D}'
+        && chars[..index].iter().rev().find(|c| !is_modifier(c)).is_some_and(is_emoji)
+        && chars.get(index + 1).is_some_and(is_emoji)
+}
+
+/// Set the aliases of tags, given as a map of each canonical tag to its aliases, such as
+/// `kubernetes` to `k8s` and `kube`. Tags that are not valid are ignored.
+pub fn set_aliases(config: &BTreeMap<String, Vec<String>>) {
+    if ALIASES.set(Aliases::new(config)).is_err() {
+        log::warn!("Aliases of tags were already set");
+    }
+}
+
+/// Set the aliases used by tests, `kubernetes-test` with the alias `k8s-test`. Aliases can only be
+/// set once, so every test that needs aliases must use these.
+#[cfg(test)]
+pub(crate) fn set_test_aliases() {
+    let config =
+        BTreeMap::from([(String::from("kubernetes-test"), vec![String::from("k8s-test")])]);
+    ALIASES.get_or_init(|| Aliases::new(&config));
+}
+
+/// Separator between the levels of a hierarchical tag, such as `lang/rust`
+pub const SEPARATOR: char = '/';
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct Tag(String);
 
 impl Tag {
     pub fn new<T: Into<String>>(tag: T) -> Result<Tag, TagError> {
-        let tag: String = Self::normalize(&tag.into());
+        let tag: String = Self::normalize(&tag.into())?;
         if tag.is_empty() {
             Err(TagError::Empty)
         } else {
@@ -23,23 +155,133 @@ impl Tag {
         }
     }
 
-    fn normalize(input: &str) -> String {
-        DISCARD.replace_all(input, "").to_lowercase().trim().to_string()
+    /// Normalize the tag to its Unicode compatibility composition (NFKC) in lowercase, so that
+    /// tags which look the same, such as a composed and decomposed `café` or a full-width `ｒｕｓｔ`,
+    /// are the same tag. Characters are discarded after normalization, since a full-width comma or
+    /// quote only becomes a comma or quote then.
+    fn normalize(input: &str) -> Result<String, TagError> {
+        let chars: Vec<char> = input.chars().filter(|c| !c.is_control()).collect();
+        let invisible: Option<&char> = chars
+            .iter()
+            .enumerate()
+            .find(|(i, c)| is_invisible(**c) && !joins_emoji(&chars, *i))
+            .map(|(_, c)| c);
+        if let Some(c) = invisible {
+            return Err(TagError::InvalidCharacter(*c));
+        }
+
+        let input: String = chars.into_iter().nfkc().collect();
+        let tag: String = DISCARD
+            .replace_all(&input, "")
+            .to_lowercase()
+            .split(SEPARATOR)
+            .map(|level| level.trim())
+            .filter(|level| !level.is_empty())
+            .join(&SEPARATOR.to_string());
+
+        Ok(tag)
+    }
+
+    /// The tag as it is used for matching, without diacritics if folding of diacritics is enabled
+    pub fn folded(&self) -> Tag {
+        Tag(fold(&self.0))
+    }
+
+    /// The tag normalized again, for tags that were stored before the current normalization, or
+    /// `None` if the tag is no longer valid
+    pub fn renormalized(&self) -> Option<Tag> {
+        Tag::new(self.0.as_str()).ok()
+    }
+
+    /// Check if the tag is the given tag or a descendant of it, so that `lang/rust` is within both
+    /// `lang` and `lang/rust`, but not within `lang/go` or `la`
+    pub fn is_within(&self, ancestor: &Tag) -> bool {
+        self == ancestor || is_descendant(&self.0, &ancestor.0)
     }
 
+    /// The tag itself and all its ancestors, such as `lang`, `lang/rust` and `lang/rust/async` for
+    /// `lang/rust/async`
+    pub fn lineage(&self) -> Vec<Tag> {
+        self.0
+            .match_indices(SEPARATOR)
+            .map(|(i, _)| Tag(self.0[..i].to_string()))
+            .chain(std::iter::once(self.clone()))
+            .collect()
+    }
+
+    /// The levels of the tag, such as `lang` and `rust` for `lang/rust`
+    pub fn levels(&self) -> std::str::Split<'_, char> {
+        self.0.split(SEPARATOR)
+    }
+
+    /// Parse tags separated by whitespace or commas, where any alias is replaced with its
+    /// canonical tag
     pub fn new_set<T: Into<String>>(tags: T) -> HashSet<Tag> {
-        TERMINATOR.split(&tags.into()).filter_map(|t| Tag::from_str(t).ok()).collect()
+        TERMINATOR
+            .split(&tags.into())
+            .filter_map(|t| match Tag::from_str(t) {
+                Ok(tag) => Some(tag),
+                Err(TagError::Empty) => None,
+                Err(e) => {
+                    log::warn!("Ignoring tag {:?}: {}", t, e);
+                    None
+                }
+            })
+            .map(Tag::canonical)
+            .collect()
+    }
+
+    /// The canonical tag if this tag is an alias, otherwise the tag itself
+    pub fn canonical(self) -> Tag {
+        match ALIASES.get() {
+            Some(aliases) => aliases.canonical(self),
+            None => self,
+        }
+    }
+
+    /// The canonical tag of this tag, followed by all its aliases, such as `kubernetes`, `k8s` and
+    /// `kube` for `k8s`
+    pub fn synonyms(&self) -> Vec<Tag> {
+        match ALIASES.get() {
+            Some(aliases) => aliases.synonyms(self),
+            None => vec![self.clone()],
+        }
     }
 }
 
+/// Check if the tag is a strict descendant of the ancestor in the tag hierarchy
+pub fn is_descendant(tag: &str, ancestor: &str) -> bool {
+    match tag.strip_prefix(ancestor) {
+        Some(rest) => rest.starts_with(SEPARATOR),
+        None => false,
+    }
+}
+
+/// Number of bookmarks that have each tag
+pub fn count<'a>(tag_sets: impl IntoIterator<Item = &'a HashSet<Tag>>) -> HashMap<Tag, usize> {
+    let mut counts: HashMap<Tag, usize> = HashMap::new();
+    for tags in tag_sets {
+        for tag in tags {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    counts
+}
+
 #[derive(Debug)]
 pub enum TagError {
     Empty,
+    InvalidCharacter(char),
 }
 
 impl Display for TagError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("Tag was empty or contained no valid characters")
+        match self {
+            TagError::Empty => f.write_str("Tag was empty or contained no valid characters"),
+            TagError::InvalidCharacter(c) => {
+                write!(f, "Tag contained invalid character {}", c.escape_unicode())
+            }
+        }
     }
 }
 
@@ -73,7 +315,7 @@ impl Ord for Tag {
 
 impl PartialOrd for Tag {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.0.cmp(&other.0))
+        Some(self.cmp(other))
     }
 }
 
@@ -101,3 +343,77 @@ impl TagHolder for Vec<Tag> {
         self.into_iter().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(tag: &str) -> Tag {
+        Tag::new(tag).unwrap()
+    }
+
+    #[test]
+    fn hierarchy_separators_are_normalized() {
+        assert_eq!(tag("/Lang//Rust/").to_string(), "lang/rust");
+        assert!(Tag::new("/").is_err());
+    }
+
+    #[test]
+    fn tag_is_within_its_ancestors() {
+        assert!(tag("lang/rust").is_within(&tag("lang")));
+        assert!(tag("lang/rust").is_within(&tag("lang/rust")));
+        assert!(!tag("lang/rust").is_within(&tag("la")));
+        assert!(!tag("lang").is_within(&tag("lang/rust")));
+    }
+
+    #[test]
+    fn aliases_are_canonicalized() {
+        let config = BTreeMap::from([(
+            String::from("Kubernetes"),
+            vec![String::from("k8s"), String::from("kube")],
+        )]);
+        let aliases = Aliases::new(&config);
+        assert_eq!(aliases.canonical(tag("k8s")), tag("kubernetes"));
+        assert_eq!(aliases.canonical(tag("rust")), tag("rust"));
+        assert_eq!(
+            aliases.synonyms(&tag("kube")),
+            vec![tag("kubernetes"), tag("k8s"), tag("kube")]
+        );
+    }
+
+    #[test]
+    fn unicode_is_normalized() {
+        assert_eq!(tag("Caf\u{e9}"), tag("cafe\u{301}"));
+        assert_eq!(tag("\u{ff32}\u{ff55}\u{ff53}\u{ff54}").to_string(), "rust");
+        assert_eq!(tag("a\u{ff0c}b\u{ff02}c\u{ff3c}d").to_string(), "abcd");
+    }
+
+    #[test]
+    fn invisible_characters_are_rejected() {
+        assert!(matches!(Tag::new("ru\u{200b}st"), Err(TagError::InvalidCharacter('\u{200b}'))));
+        assert!(matches!(Tag::new("a\u{200d}b"), Err(TagError::InvalidCharacter('\u{200d}'))));
+    }
+
+    #[test]
+    fn control_characters_and_whitespace_are_stripped() {
+        assert_eq!(Tag::new("\trust\n").unwrap().as_ref(), "rust");
+        assert_eq!(Tag::new("ru\u{7}st").unwrap().as_ref(), "rust");
+    }
+
+    #[test]
+    fn emoji_joined_by_zero_width_joiner_are_kept() {
+        assert_eq!(Tag::new("👩\u{200d}💻").unwrap().as_ref(), "👩\u{200d}💻");
+        assert_eq!(Tag::new("👩🏽\u{200d}💻").unwrap().as_ref(), "👩🏽\u{200d}💻");
+    }
+
+    #[test]
+    fn diacritics_are_folded() {
+        assert_eq!(fold_diacritics("caf\u{e9}/na\u{ef}ve"), "cafe/naive");
+    }
+
+    #[test]
+    fn lineage_starts_at_root() {
+        let lineage: Vec<Tag> = tag("lang/rust/async").lineage();
+        assert_eq!(lineage, vec![tag("lang"), tag("lang/rust"), tag("lang/rust/async")]);
+    }
+}
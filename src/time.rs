@@ -0,0 +1,103 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::bookmark::Bookmark;
+
+/// Current time, truncated to whole seconds since that is the precision timestamps are stored with
+pub fn now() -> SystemTime {
+    let secs: u64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// Format a timestamp as RFC 3339 in UTC, such as `2024-05-01T12:30:00Z`
+pub fn format(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+/// Parse a point in time given either as a date or date and time, such as `2024-05-01` or
+/// `2024-05-01T12:30:00Z`, or as a duration before now, such as `7days` or `2w`
+pub fn parse(input: &str) -> Result<SystemTime, String> {
+    // A date is the start of that day, which is only a valid date and time once the time is added
+    let date_time = humantime::parse_rfc3339_weak(input)
+        .or_else(|_| humantime::parse_rfc3339_weak(&format!("{}T00:00:00Z", input)));
+    if let Ok(time) = date_time {
+        return Ok(time);
+    }
+    humantime::parse_duration(input)
+        .ok()
+        .and_then(|duration| now().checked_sub(duration))
+        .ok_or_else(|| {
+            format!("expected a date such as 2024-05-01 or a duration such as 7days: {}", input)
+        })
+}
+
+/// Bookmarks created within a period of time, where either end of the period may be open
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Period {
+    since: Option<SystemTime>,
+    before: Option<SystemTime>,
+}
+
+impl Period {
+    pub fn new(since: Option<SystemTime>, before: Option<SystemTime>) -> Period {
+        Period { since, before }
+    }
+
+    /// Check if the bookmark was created within the period. A bookmark without a creation time is
+    /// only within a period that is open at both ends.
+    pub fn contains(&self, bkm: &Bookmark) -> bool {
+        if self.since.is_none() && self.before.is_none() {
+            return true;
+        }
+        match bkm.created() {
+            Some(created) => {
+                self.since.map(|since| created >= since).unwrap_or(true)
+                    && self.before.map(|before| created < before).unwrap_or(true)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Serialization of optional timestamps as RFC 3339, for use with `#[serde(with = "...")]`
+pub mod rfc3339 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(time: &Option<SystemTime>, s: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => s.serialize_str(&super::format(*time)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SystemTime>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(time) => {
+                humantime::parse_rfc3339_weak(&time).map(Some).map_err(serde::de::Error::custom)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_dates_and_durations() {
+        let date: SystemTime = parse("2024-05-01").unwrap();
+        assert_eq!(format(date), "2024-05-01T00:00:00Z");
+        let date_time: SystemTime = parse("2024-05-01T12:30:00Z").unwrap();
+        assert_eq!(format(date_time), "2024-05-01T12:30:00Z");
+        let week_ago: SystemTime = parse("7days").unwrap();
+        let age: u64 = now().duration_since(week_ago).unwrap().as_secs();
+        assert!((7 * 24 * 60 * 60..=7 * 24 * 60 * 60 + 1).contains(&age));
+        assert!(parse("last tuesday").is_err());
+        assert!(parse("500000000000years").is_err());
+        assert!(parse("2024-05-01T").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::bookmark::{self, FileError};
+
+/// Visits are kept outside of the data directory, in a directory in the local data directory of
+/// the user, so that opening a bookmark never changes anything in a data directory that may be
+/// synchronized with other machines. The name can not be mistaken for the directory of a profile.
+const VISITS_DIR: &str = "goto_visits";
+
+/// Only the most recent visits of each bookmark are kept
+const MAX_VISITS: usize = 10;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Weight of a visit depending on how many days ago it was made, where visits older than the last
+/// bucket get the lowest weight
+const BUCKETS: [(u64, f64); 4] = [(4, 100.0), (14, 70.0), (31, 50.0), (90, 30.0)];
+const OLD_VISIT_WEIGHT: f64 = 10.0;
+
+/// Record of when each bookmark was opened, used to rank bookmarks that are opened frequently and
+/// recently above those that are not
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Visits {
+    #[serde(flatten)]
+    visits: HashMap<Url, Vec<u64>>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Visits {
+    /// Load the visits of the bookmarks in the data directory `dir`. Visits are not saved if the
+    /// user has no local data directory.
+    pub fn load(dir: &Path) -> Visits {
+        match local_path(dir) {
+            Some(path) => Visits::read(&path),
+            None => Visits::default(),
+        }
+    }
+
+    fn read(path: &Path) -> Visits {
+        let visits: Visits = match std::fs::read(path) {
+            Ok(bytes) => serde_yaml::from_slice(&bytes).unwrap_or_else(|e| {
+                log::warn!("Unable to read visits {:?}: {}", path, e);
+                Visits::default()
+            }),
+            Err(_) => Visits::default(),
+        };
+
+        Visits {
+            path: path.to_path_buf(),
+            ..visits
+        }
+    }
+
+    /// Record a visit of the URL at the current time and save all visits
+    pub fn record(&mut self, url: &Url) -> Result<(), FileError> {
+        let visits: &mut Vec<u64> = self.visits.entry(url.clone()).or_default();
+        visits.push(now());
+        if visits.len() > MAX_VISITS {
+            visits.drain(..visits.len() - MAX_VISITS);
+        }
+        if self.path.as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let yaml: String = serde_yaml::to_string(self).map_err(|_| FileError::Serialize)?;
+        std::fs::write(&self.path, yaml)?;
+        Ok(())
+    }
+
+    /// Frecency of the URL, a combination of how often and how recently it was visited. A URL that
+    /// has never been visited has a frecency of 0.0.
+    pub fn frecency(&self, url: &Url) -> f64 {
+        let now: u64 = now();
+        match self.visits.get(url) {
+            Some(visits) => visits.iter().map(|visit| weight(now.saturating_sub(*visit))).sum(),
+            None => 0.0,
+        }
+    }
+}
+
+/// Path of the file with the visits of the bookmarks in the data directory `dir`, named by a hash
+/// of the path of `dir` so that each profile and data directory has visits of its own
+fn local_path(dir: &Path) -> Option<PathBuf> {
+    let dir: PathBuf = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let name: String = format!("{}.yaml", bookmark::hash(&dir.to_string_lossy()));
+    dirs_next::data_local_dir().map(|local| local.join(VISITS_DIR).join(name))
+}
+
+fn weight(age: u64) -> f64 {
+    BUCKETS
+        .iter()
+        .find(|(days, _)| age <= days * DAY)
+        .map(|(_, weight)| *weight)
+        .unwrap_or(OLD_VISIT_WEIGHT)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::temp_dir;
+
+    #[test]
+    fn recent_visits_weigh_more_than_old_visits() {
+        let url = Url::parse("https://example.com").unwrap();
+        let now: u64 = now();
+        let mut recent = Visits::default();
+        recent.visits.insert(url.clone(), vec![now - DAY]);
+        let mut old = Visits::default();
+        old.visits.insert(url.clone(), vec![now - 100 * DAY, now - 60 * DAY]);
+
+        assert_eq!(recent.frecency(&url), 100.0);
+        assert_eq!(old.frecency(&url), 40.0);
+    }
+
+    #[test]
+    fn recorded_visits_are_saved() {
+        let path: PathBuf = temp_dir("visits").join("visits.yaml");
+        let url = Url::parse("https://example.com").unwrap();
+        Visits::read(&path).record(&url).unwrap();
+
+        assert_eq!(Visits::read(&path).frecency(&url), 100.0);
+    }
+
+    #[test]
+    fn unvisited_url_has_no_frecency() {
+        let url = Url::parse("https://example.com").unwrap();
+        assert_eq!(Visits::default().frecency(&url), 0.0);
+    }
+}